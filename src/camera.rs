@@ -5,11 +5,23 @@ use rand::Rng;
 
 use std::f32::consts::PI;
 
-use crate::hittable::{Hittable, HittableList};
-use crate::material::Scatter;
+use crate::fog::Fog;
+use crate::hittable::{HitRecord, Hittable, HittableList};
+use crate::light::Illuminate;
+use crate::light_sampler::LightSampling;
+use crate::material::{Material, Scatter};
+use crate::medium::{Interaction, Sample};
 use crate::vec3::{Point, Color};
 use crate::interval::Interval;
-use crate::ray::Ray;
+use crate::ray::{Ray, RayKind};
+use crate::sky::Sky;
+use crate::spectrum::Wavelengths;
+
+// free flight events in a row inside a medium after which the path is dropped
+const MAX_INTERIOR_EVENTS: usize = 1024;
+
+// shadow rays stop this fraction of their length short of the light, not to hit the emitter itself
+const SHADOW_EPSILON: f32 = 1e-3;
 
 pub fn degrees_to_radians(degrees: f32) -> f32 {
     return degrees * PI / 180.0;
@@ -30,6 +42,23 @@ pub struct Camera {
     pub defocus_angle: f32,
     pub focus_distance: f32,
 
+    // trace wavelengths instead of rgb, for dispersion
+    pub spectral: bool,
+
+    // scene wide fog, applied to every ray segment outside of objects; under a sky, Fog::set_sky
+    // keeps its in-scattered light in line with it
+    pub fog: Option<Fog>,
+
+    // radiance of the rays leaving the scene, the sky gradient if none (e.g. black for interiors)
+    pub background: Option<Color>,
+
+    // physical sky seen by the rays leaving the scene when there is no background, its sun
+    // disk only on the paths where the sun light itself is not sampled
+    pub sky: Option<Sky>,
+
+    // how the lights are picked for the shadow rays, all of them by default
+    pub light_sampling: LightSampling,
+
     image_height: i32,
     center: Point,
     pixel_00_loc: Point,
@@ -46,6 +75,7 @@ pub struct Camera {
 
 impl Camera {
     
+    #[allow(clippy::too_many_arguments, clippy::field_reassign_with_default)]
     pub fn new(aspect_ratio: f32, image_width: i32, samples_per_pixel: i32, max_depth: i32, 
         v_fov: f32, look_from: Point, look_at: Point, v_up: Point, defocus_angle: f32, focus_distance: f32) -> Self {
 
@@ -93,9 +123,16 @@ impl Camera {
                     .map(|_| {
 
                         let ray = self.get_ray(i, j);
-                        let color = self.ray_color(&ray, self.max_depth, &world);
 
-                        return color;
+                        if ! self.spectral { return self.ray_color(&ray, self.max_depth, world, true, None) }
+
+                        // hero wavelength sampling, the path radiance is estimated at three wavelengths
+                        let wavelengths = Wavelengths::sample(Point::random_float());
+                        let ray = ray.set_wavelengths(Some(wavelengths));
+                        let values = self.ray_color(&ray, self.max_depth, world, true, None);
+
+                        // the wavelengths may have been terminated along the path, the values account for it
+                        return wavelengths.to_rgb(values);
 
                     }).sum();
                     
@@ -194,28 +231,204 @@ impl Camera {
 
     }
 
-    fn ray_color(&self, ray: &Ray, depth: i32, world: &HittableList) -> Color {
+    // specular rays come from the camera or from perfectly specular lobes, only they see the
+    // emitters, the light reaching the other bounces is gathered from the lights at their origin
+    // specular tells whether the emitters hit are counted, from is the object the ray left
+    fn ray_color(&self, ray: &Ray, depth: i32, world: &HittableList, specular: bool, from: Option<usize>) -> Color {
 
         // stop gathering light if depth is exceeded
         if depth <= 0 { return Color::default() }
 
         let interval = Interval::universe().set_min(0.001);
+
+        // a ray leaving an object through a back face travelled inside it, through the medium
+        // of its material if any: random walk in there until the ray reaches the surface,
+        // sampled on one channel and weighted by the path pdf averaged over the channels
+        let mut ray = ray.clone();
+        let mut hit = world.hit(&ray, interval);
+        let mut value = Color::new(1.0, 1.0, 1.0);
+        let mut pdf = Color::new(1.0, 1.0, 1.0);
+        let channel = ((Point::random_float()*3.0) as usize).min(2);
+        let mut events = 0;
+        let mut emitted = Color::default();
+
+        while let Some(medium) = hit.as_ref().filter(|(record, _)| !record.front_face).and_then(|(_, material)| material.interior()) {
+
+            let t_max = hit.as_ref().map_or(f32::INFINITY, |(record, _)| record.t);
+
+            match medium.sample(&ray, t_max, channel) {
+                Interaction::Scattered(ray_out, event_value, event_pdf, emission) => {
+                    emitted = emitted + value*emission*(3.0 / (pdf.x() + pdf.y() + pdf.z()));
+                    value = value*event_value;
+                    pdf = pdf*event_pdf;
+
+                    // the lights seen from the event, scattered by the phase function towards the ray
+                    let (phase, incoming) = (medium.phase(), Point::unit_vector(&ray.direction()));
+                    let object = hit.as_ref().map_or(0, |(record, _)| record.object);
+                    let direct = self.sample_lights(&ray, ray_out.origin(), Point::default(), object, world, |direction| {
+                        Color::new(1.0, 1.0, 1.0)*phase.evaluate(incoming.dot(Point::unit_vector(&direction)))
+                    });
+                    emitted = emitted + value*direct*(3.0 / (pdf.x() + pdf.y() + pdf.z()));
+
+                    ray = ray_out.set_kind(ray.kind());
+                    hit = world.hit(&ray, interval);
+                }
+                Interaction::Passed(event_value, event_pdf) => {
+                    value = value*event_value;
+                    pdf = pdf*event_pdf;
+                    break;
+                }
+            }
+
+            // both are rescaled by the same factor to stay in range, their ratio is what matters
+            let average_pdf = (pdf.x() + pdf.y() + pdf.z()) / 3.0;
+            if average_pdf <= 0.0 { return emitted }
+            value = value / average_pdf;
+            pdf = pdf / average_pdf;
+
+            events += 1;
+            if events >= MAX_INTERIOR_EVENTS { return emitted }
+
+        }
+
+        let throughput = value * (3.0 / (pdf.x() + pdf.y() + pdf.z()));
+
+        // the lights were sampled at the events inside media, the emitters hit after them were seen there
+        let specular = specular && events == 0;
+
+        let (radiance, t) = if let Some((hit, material)) = &hit {
+
+            // emitters only light the objects they are linked to, even through specular paths
+            let linked = from.is_none_or(|object| world.emitter_lights(hit.object).iter().all(|&light| world.illuminates(light, object)));
+            let emission = if specular && linked { material.emitted(&ray, hit) } else { Color::default() };
+            let direct = self.direct_light(&ray, hit, material, world);
+
+            let radiance = if let Some((ray_out, attenuation, scattered)) = material.scatter(&ray, hit) {
+
+                // scattered rays keep the wavelengths of the path unless the material changed them
+                let ray_out = match ray_out.wavelengths() {
+                    Some(_) => ray_out,
+                    None => ray_out.set_wavelengths(ray.wavelengths()),
+                };
+
+                // the boundary of a volume is index matched, going through it is not a scattering
+                // event: the path keeps its kind, the object it left and whether it sees emitters;
+                // the surface of a subsurface object left after events inside was already crossed
+                // by the shadow rays of those events
+                let (kind, specular, from) = match material {
+                    Material::Volume(_) => (ray.kind(), specular, from),
+                    _ => {
+                        // the normal faces the incoming ray
+                        let kind = if ray_out.direction().dot(hit.normal) >= 0.0 { RayKind::Reflection } else { RayKind::Refraction };
+                        let left = events > 0 && !hit.front_face && material.interior().is_some();
+                        (kind, scattered && !left, Some(hit.object))
+                    }
+                };
+
+                attenuation*self.ray_color(&ray_out.set_kind(kind), depth-1, world, specular, from)
+
+            } else { Color::default() };
+
+            (emission + direct + radiance, hit.t)
+
+        } else if let Some(background) = self.background {
+
+            (ray.spectrum(background), f32::INFINITY)
+
+        } else if let Some(sky) = &self.sky {
+
+            let sun = if specular { sky.sun_radiance(ray.direction()) } else { Color::default() };
+
+            (ray.spectrum(sky.radiance(ray.direction()) + sun), f32::INFINITY)
+
+        } else {
+
+            let unit_direction = Point::unit_vector(&ray.direction());
+            let a = 0.5*(unit_direction.y() + 1.0);
+
+            (ray.spectrum(Color::new(1.0, 1.0, 1.0)*(1.0-a) + Color::new(0.5, 0.7, 1.0)*a), f32::INFINITY)
+
+        };
+
+        // the fog fills the space between objects, not their inside
+        let outside = hit.as_ref().is_none_or(|(record, _)| record.front_face);
+        let radiance = match &self.fog {
+            Some(fog) if outside => fog.apply(&ray, t, radiance),
+            _ => radiance,
+        };
+
+        return emitted + throughput*radiance;
     
-        if let Some((hit, material)) = world.hit(ray, interval) {
+    }
+
+    // light reaching the hit straight from the light sources, one shadow ray each
+    fn direct_light(&self, ray: &Ray, hit: &HitRecord, material: &Material, world: &HittableList) -> Color {
+        return self.sample_lights(ray, hit.hit_location, hit.normal, hit.object, world, |direction| material.eval(ray, hit, direction));
+    }
+
+    // light from the lights linked to object scattered towards the ray at point, f being the bsdf
+    // times the cosine, or the phase function inside media where the normal is zero
+    fn sample_lights<F: Fn(Point) -> Color>(&self, ray: &Ray, point: Point, normal: Point, object: usize, world: &HittableList, f: F) -> Color {
+
+        if self.light_sampling == LightSampling::All {
+            return (0..world.lights().len())
+                .fold(Color::default(), |direct, light| direct + self.light_contribution(light, ray, point, object, world, &f));
+        }
+
+        // a single light, weighted by the inverse of the probability of picking it
+        let Some((index, pmf)) = world.light_sampler().sample(self.light_sampling, point, normal) else { return Color::default() };
+
+        return self.light_contribution(index, ray, point, object, world, &f) / pmf;
+
+    }
+
+    fn light_contribution<F: Fn(Point) -> Color>(&self, light: usize, ray: &Ray, point: Point, object: usize, world: &HittableList, f: &F) -> Color {
+
+        if !world.illuminates(light, object) { return Color::default() }
+
+        let Some((direction, distance, irradiance)) = world.lights()[light].illuminate(point) else { return Color::default() };
+
+        let f = f(direction);
 
-            if let Some((ray_out, attenuation)) = material.scatter(ray, &hit) {
+        if f == Color::default() { return Color::default() }
 
-                return attenuation*self.ray_color(&ray_out, depth-1, world);
+        let shadow_ray = Ray::new(point, direction).set_wavelengths(ray.wavelengths()).set_kind(RayKind::Shadow);
 
-            } else { return Color::default() }
+        return f*ray.spectrum(irradiance)*self.transmittance(&shadow_ray, distance*(1.0 - SHADOW_EPSILON), world);
+
+    }
+
+    // fraction of the light going through along a unit direction ray up to distance: the boundaries
+    // of volumes let it through their medium, and subsurface objects out of theirs, any other
+    // surface stops it
+    fn transmittance(&self, ray: &Ray, distance: f32, world: &HittableList) -> Color {
+
+        let mut transmittance = Color::new(1.0, 1.0, 1.0);
+        let mut segment = ray.clone();
+        let mut remaining = distance;
+
+        while let Some((record, material)) = world.hit(&segment, Interval::universe().set_min(0.001).set_max(remaining)) {
+
+            let Some(through) = material.shadow_transmittance(&segment, &record) else { return Color::default() };
+
+            // a back face closes a segment through the medium
+            if let Some(medium) = material.interior().filter(|_| !record.front_face) {
+                transmittance = transmittance*medium.transmittance(&segment, record.t);
+            }
+
+            transmittance = transmittance*through;
+
+            segment = Ray::new(record.hit_location, segment.direction()).set_wavelengths(segment.wavelengths()).set_kind(RayKind::Shadow);
+            remaining -= record.t;
 
         }
-    
-        let unit_direction = Point::unit_vector(&ray.direction());
-        let a = 0.5*(unit_direction.y() + 1.0);
-    
-        return Color::new(1.0, 1.0, 1.0)*(1.0-a) + Color::new(0.5, 0.7, 1.0)*a;
-    
+
+        if let Some(fog) = &self.fog {
+            transmittance = transmittance*(-fog.optical_depth(ray, distance)).exp();
+        }
+
+        return transmittance;
+
     }
 
 }
@@ -234,6 +447,11 @@ impl Default for Camera {
             v_up: Point::default(),
             defocus_angle: 0.0,
             focus_distance: 0.0,
+            spectral: false,
+            fog: None,
+            background: None,
+            sky: None,
+            light_sampling: LightSampling::All,
             image_height: 0,
             center: Point::default(),
             pixel_00_loc: Point::default(),
@@ -249,3 +467,105 @@ impl Default for Camera {
     }
 
 }
+
+//
+// tests
+#[test]
+fn test_volume_boundary(){
+
+    use crate::constant_medium::ConstantMedium;
+    use crate::material::{DiffuseLight, Lambertian};
+    use crate::medium::Phase;
+    use crate::quad::Quad;
+    use crate::sphere::Sphere;
+
+    let camera = Camera { background: Some(Color::default()), ..Camera::default() };
+
+    // an emitter behind an empty medium, seen along a path that already sampled the lights
+    let mut world = HittableList::new();
+    world.add(Quad::new(Point::new(-1.0, -1.0, -3.0), Point::new(2.0, 0.0, 0.0), Point::new(0.0, 2.0, 0.0),
+        DiffuseLight::new(Color::new(1.0, 1.0, 1.0))));
+    world.add(ConstantMedium::new(Sphere::new(Point::default(), 1.0, Lambertian::new(Color::default())),
+        0.0, Color::new(1.0, 1.0, 1.0), Phase::Isotropic));
+
+    let ray = Ray::new(Point::new(0.0, 0.0, 2.0), Point::new(0.0, 0.0, -1.0));
+
+    // crossing the boundary does not make the emitter visible again, nor hide it from camera rays
+    assert_eq!(camera.ray_color(&ray, 10, &world, false, None), Color::default());
+    assert_eq!(camera.ray_color(&ray, 10, &world, true, None), Color::new(1.0, 1.0, 1.0));
+
+}
+
+#[test]
+fn test_volume_visibility(){
+
+    use crate::constant_medium::ConstantMedium;
+    use crate::hittable::Visibility;
+    use crate::material::{DiffuseLight, Lambertian};
+    use crate::medium::Phase;
+    use crate::quad::Quad;
+    use crate::sphere::Sphere;
+
+    let camera = Camera { background: Some(Color::default()), ..Camera::default() };
+
+    // an object only camera rays see, behind an empty medium
+    let mut world = HittableList::new();
+    let backdrop = world.add(Quad::new(Point::new(-1.0, -1.0, -3.0), Point::new(2.0, 0.0, 0.0), Point::new(0.0, 2.0, 0.0),
+        DiffuseLight::new(Color::new(1.0, 1.0, 1.0))));
+    world.set_visibility(backdrop, Visibility { camera: true, shadow: false, reflection: false, refraction: false });
+    world.add(ConstantMedium::new(Sphere::new(Point::default(), 1.0, Lambertian::new(Color::default())),
+        0.0, Color::new(1.0, 1.0, 1.0), Phase::Isotropic));
+
+    // camera rays stay camera rays through the boundaries, reflected ones still miss it
+    let ray = Ray::new(Point::new(0.0, 0.0, 2.0), Point::new(0.0, 0.0, -1.0));
+    assert_eq!(camera.ray_color(&ray, 10, &world, true, None), Color::new(1.0, 1.0, 1.0));
+    assert_eq!(camera.ray_color(&ray.set_kind(RayKind::Reflection), 10, &world, true, None), Color::default());
+
+}
+
+#[test]
+fn test_medium_lights(){
+
+    use crate::constant_medium::ConstantMedium;
+    use crate::light::PointLight;
+    use crate::material::Lambertian;
+    use crate::medium::Phase;
+    use crate::sphere::Sphere;
+
+    let camera = Camera { background: Some(Color::default()), ..Camera::default() };
+
+    // a cloud lit only by a point light, which no ray can hit
+    let mut world = HittableList::new();
+    world.add(ConstantMedium::new(Sphere::new(Point::default(), 1.0, Lambertian::new(Color::default())),
+        2.0, Color::new(1.0, 1.0, 1.0), Phase::Isotropic));
+    world.add_light(PointLight::new(Point::new(0.0, 3.0, 0.0), Color::new(10.0, 10.0, 10.0)));
+
+    let ray = Ray::new(Point::new(0.0, 0.0, 3.0), Point::new(0.0, 0.0, -1.0));
+    let n = 1000;
+    let total: Color = (0..n).map(|_| camera.ray_color(&ray, 50, &world, true, None)).sum();
+
+    assert!(total.x() / n as f32 > 0.05);
+
+}
+
+#[test]
+fn test_subsurface_lights(){
+
+    use crate::light::PointLight;
+    use crate::material::Subsurface;
+    use crate::sphere::Sphere;
+
+    let camera = Camera { background: Some(Color::default()), ..Camera::default() };
+
+    // a translucent ball lit only by a point light, its color only comes from the events inside
+    let mut world = HittableList::new();
+    world.add(Sphere::new(Point::default(), 1.0, Subsurface::new(Color::new(0.9, 0.9, 0.9), Color::new(0.2, 0.2, 0.2), 1.5)));
+    world.add_light(PointLight::new(Point::new(0.0, 3.0, 0.0), Color::new(10.0, 10.0, 10.0)));
+
+    let ray = Ray::new(Point::new(0.0, 0.0, 3.0), Point::new(0.0, 0.0, -1.0));
+    let n = 1000;
+    let total: Color = (0..n).map(|_| camera.ray_color(&ray, 50, &world, true, None)).sum();
+
+    assert!(total.x() / n as f32 > 0.01);
+
+}
@@ -0,0 +1,60 @@
+use crate::{ray::Ray, interval::Interval};
+use crate::hittable::{Hittable, HitRecord, HittableObject};
+use crate::material::{Material, Volume};
+use crate::medium::{Homogeneous, Phase};
+use crate::vec3::Color;
+
+//
+// constant density medium filling a closed boundary
+pub struct ConstantMedium {
+    boundary: Box<HittableObject>,
+    material: Material,
+}
+
+impl ConstantMedium {
+
+    // the material of the boundary is ignored, the boundary only delimits the medium
+    pub fn new(boundary: HittableObject, density: f32, albedo: Color, phase: Phase) -> HittableObject {
+
+        let medium = Homogeneous::new(Color::new(density, density, density), albedo, phase);
+
+        HittableObject::ConstantMedium(Self { boundary: Box::new(boundary), material: Volume::new(medium) })
+
+    }
+
+}
+
+impl Hittable for ConstantMedium {
+
+    fn hit(&self, ray: &Ray, interval: Interval) -> Option<(HitRecord, &Material)> {
+
+        // the free flights are sampled by the camera between the boundary hits
+        let (record, _) = self.boundary.hit(ray, interval)?;
+
+        return Some((record, &self.material));
+
+    }
+
+}
+
+//
+// tests
+#[test]
+fn test_constant_medium(){
+
+    use crate::material::Lambertian;
+    use crate::sphere::Sphere;
+    use crate::vec3::Point;
+
+    let boundary = Sphere::new(Point::default(), 1.0, Lambertian::new(Color::default()));
+    let fog = ConstantMedium::new(boundary, 0.5, Color::new(1.0, 1.0, 1.0), Phase::Isotropic);
+
+    // the boundary is hit like the wrapped object, with a material that carries the medium
+    let ray = Ray::new(Point::new(0.0, 0.0, -3.0), Point::new(0.0, 0.0, 1.0));
+    let (record, material) = fog.hit(&ray, Interval::universe().set_min(0.001)).expect("The ray hits the boundary.");
+
+    assert_relative_eq!(record.t, 2.0);
+    assert!(record.front_face);
+    assert!(material.interior().is_some());
+
+}
@@ -0,0 +1,137 @@
+use crate::{vec3::Point, ray::Ray, interval::Interval};
+use crate::hittable::{Hittable, HitRecord, HittableObject};
+use crate::light::Light;
+use crate::material::Material;
+use crate::quad::Quad;
+
+//
+// box made of six quads facing outwards, each with its own [0:1]^2 uv
+pub struct Cuboid {
+    sides: Vec<Quad>,
+}
+
+impl Cuboid {
+
+    // axis-aligned, between two opposite corners
+    pub fn new(a: Point, b: Point, material: Material) -> HittableObject {
+
+        let min = Point::new(a.x().min(b.x()), a.y().min(b.y()), a.z().min(b.z()));
+        let max = Point::new(a.x().max(b.x()), a.y().max(b.y()), a.z().max(b.z()));
+        let size = max - min;
+
+        Cuboid::oriented(min, [Point::new(size.x(), 0.0, 0.0), Point::new(0.0, size.y(), 0.0), Point::new(0.0, 0.0, size.z())], material)
+
+    }
+
+    // axis-aligned box of the given size turned by angle degrees around the vertical through its center,
+    // resting on its bottom face
+    pub fn rotated(bottom_center: Point, size: Point, angle: f32, material: Material) -> HittableObject {
+
+        let (sin, cos) = angle.to_radians().sin_cos();
+        let x = Point::new(cos, 0.0, -sin)*size.x();
+        let z = Point::new(sin, 0.0, cos)*size.z();
+        let corner = bottom_center - x/2.0 - z/2.0;
+
+        Cuboid::oriented(corner, [x, Point::new(0.0, size.y(), 0.0), z], material)
+
+    }
+
+    // parallelepiped spanned by three edges from a corner
+    pub fn oriented(corner: Point, edges: [Point; 3], material: Material) -> HittableObject {
+
+        // right-handed edges, so that u x v of the sides below points outwards
+        let [a, b, c] = if edges[0].cross(edges[1]).dot(edges[2]) < 0.0 { [edges[1], edges[0], edges[2]] } else { edges };
+
+        let sides = vec![
+            Quad::side(corner, b, a, material.clone()),
+            Quad::side(corner + c, a, b, material.clone()),
+            Quad::side(corner, a, c, material.clone()),
+            Quad::side(corner + b, c, a, material.clone()),
+            Quad::side(corner, c, b, material.clone()),
+            Quad::side(corner + a, b, c, material),
+        ];
+
+        HittableObject::Cuboid(Self { sides })
+
+    }
+
+    // an emitting box is sampled through its six sides
+    pub fn area_lights(&self) -> Vec<Light> {
+        self.sides.iter().filter_map(Quad::area_light).collect()
+    }
+
+}
+
+impl Hittable for Cuboid {
+
+    fn hit(&self, ray: &Ray, interval: Interval) -> Option<(HitRecord, &Material)> {
+
+        let mut closest = None;
+        let mut interval = interval;
+
+        for side in &self.sides {
+            if let Some((record, material)) = side.hit(ray, interval) {
+                interval = interval.set_max(record.t);
+                closest = Some((record, material));
+            }
+        }
+
+        return closest;
+
+    }
+
+}
+
+//
+// tests
+#[test]
+fn test_cuboid(){
+
+    use crate::hittable::HittableList;
+    use crate::material::{DiffuseLight, Lambertian};
+
+    let material = Lambertian::new(Point::new(0.5, 0.5, 0.5));
+    let interval = Interval::universe().set_min(0.001);
+    let cuboid = Cuboid::new(Point::new(1.0, 1.0, 1.0), Point::new(-1.0, 0.0, -2.0), material.clone());
+
+    // outward normals on every side, seen from outside
+    let rays = [
+        (Point::new(0.0, 0.5, 5.0), Point::new(0.0, 0.0, -1.0), Point::new(0.0, 0.0, 1.0), 4.0),
+        (Point::new(0.0, 0.5, -5.0), Point::new(0.0, 0.0, 1.0), Point::new(0.0, 0.0, -1.0), 3.0),
+        (Point::new(3.0, 0.5, 0.0), Point::new(-1.0, 0.0, 0.0), Point::new(1.0, 0.0, 0.0), 2.0),
+        (Point::new(-3.0, 0.5, 0.0), Point::new(1.0, 0.0, 0.0), Point::new(-1.0, 0.0, 0.0), 2.0),
+        (Point::new(0.0, 3.0, 0.0), Point::new(0.0, -1.0, 0.0), Point::new(0.0, 1.0, 0.0), 2.0),
+        (Point::new(0.0, -3.0, 0.0), Point::new(0.0, 1.0, 0.0), Point::new(0.0, -1.0, 0.0), 3.0),
+    ];
+
+    for (origin, direction, normal, t) in rays {
+        let (record, _) = cuboid.hit(&Ray::new(origin, direction), interval).expect("The ray hits the box.");
+        assert!(record.front_face);
+        assert_eq!(record.normal, normal);
+        assert_relative_eq!(record.t, t);
+        assert!((0.0..=1.0).contains(&record.u) && (0.0..=1.0).contains(&record.v));
+    }
+
+    // from inside, the far side seen from behind
+    let (record, _) = cuboid.hit(&Ray::new(Point::new(0.0, 0.5, 0.0), Point::new(0.0, 0.0, 1.0)), interval).expect("The ray leaves the box.");
+    assert!(!record.front_face);
+    assert_relative_eq!(record.t, 1.0);
+
+    // a unit cube turned by 45 degrees shows an edge at half a diagonal from its center
+    let cuboid = Cuboid::rotated(Point::default(), Point::new(1.0, 1.0, 1.0), 45.0, material.clone());
+    let (record, _) = cuboid.hit(&Ray::new(Point::new(0.0, 0.5, 5.0), Point::new(0.0, 0.0, -1.0)), interval).expect("The ray hits the box.");
+    assert_relative_eq!(record.t, 5.0 - (0.5f32).sqrt(), epsilon = 1e-4);
+
+    // left-handed edges give the same outward box
+    let cuboid = Cuboid::oriented(Point::default(), [Point::new(0.0, 0.0, 1.0), Point::new(0.0, 1.0, 0.0), Point::new(1.0, 0.0, 0.0)], material);
+    let (record, _) = cuboid.hit(&Ray::new(Point::new(0.5, 0.5, 3.0), Point::new(0.0, 0.0, -1.0)), interval).expect("The ray hits the box.");
+    assert!(record.front_face);
+    assert_eq!(record.normal, Point::new(0.0, 0.0, 1.0));
+
+    // an emitting box is sampled through its six sides
+    let mut world = HittableList::new();
+    let lamp = world.add(Cuboid::new(Point::default(), Point::new(1.0, 1.0, 1.0), DiffuseLight::new(Point::new(1.0, 1.0, 1.0))));
+    assert_eq!(world.emitter_lights(lamp).len(), 6);
+    assert_eq!(world.lights().len(), 6);
+
+}
@@ -0,0 +1,90 @@
+use crate::{vec3::Point, ray::Ray, interval::Interval};
+use crate::hittable::{Hittable, HitRecord, HittableObject};
+use crate::light::{AreaLight, Light};
+use crate::material::Material;
+use crate::onb::Onb;
+
+use std::f32::consts::PI;
+
+//
+// flat disk facing its normal
+pub struct Disk {
+    center: Point,
+    radius: f32,
+    frame: Onb,
+    material: Material,
+}
+
+impl Disk {
+
+    pub fn new(center: Point, normal: Point, radius: f32, material: Material) -> HittableObject {
+        HittableObject::Disk(Self { center, radius, frame: Onb::new(Point::unit_vector(&normal)), material })
+    }
+
+    pub fn area_light(&self) -> Option<Light> {
+        self.material.emission().map(|radiance| AreaLight::disk(self.center, self.frame.w(), self.radius, radiance))
+    }
+
+}
+
+impl Hittable for Disk {
+
+    fn hit(&self, ray: &Ray, interval: Interval) -> Option<(HitRecord, &Material)> {
+
+        let normal = self.frame.w();
+        let denominator = normal.dot(ray.direction());
+
+        if denominator.abs() < 1e-8 { return None }
+
+        let root = normal.dot(self.center - ray.origin()) / denominator;
+
+        if ! interval.surrounds(root) { return None }
+
+        let hit_location = ray.at(root);
+        let local = self.frame.to_local(hit_location - self.center);
+        let r = (local.x()*local.x() + local.y()*local.y()).sqrt();
+
+        if r > self.radius { return None }
+
+        // u = phi/2pi around the normal, v = 1 - r/radius from the rim to the center (as in pbrt),
+        // dpdu x dpdv points along the normal; r is clamped so that the center keeps a frame
+        let phi = local.y().atan2(local.x());
+        let phi = if phi < 0.0 { phi + 2.0*PI } else { phi };
+        let (u, v) = (phi / (2.0*PI), 1.0 - r/self.radius);
+
+        let r = r.max(1e-4*self.radius);
+        let dpdu = self.frame.to_world(Point::new(-phi.sin(), phi.cos(), 0.0)) * (2.0*PI*r);
+        let dpdv = self.frame.to_world(Point::new(-phi.cos(), -phi.sin(), 0.0)) * self.radius;
+
+        let record = HitRecord::new(hit_location, normal, root, ray, u, v, dpdu, dpdv);
+
+        return Some((record, &self.material));
+
+    }
+
+}
+
+//
+// tests
+#[test]
+fn test_disk(){
+
+    use crate::material::Lambertian;
+
+    let material = Lambertian::new(Point::new(0.5, 0.5, 0.5));
+    let disk = Disk::new(Point::new(0.0, 1.0, 0.0), Point::new(0.0, 2.0, 0.0), 2.0, material);
+    let interval = Interval::universe().set_min(0.001);
+
+    let ray = Ray::new(Point::new(1.0, 3.0, 0.0), Point::new(0.0, -1.0, 0.0));
+    let (record, _) = disk.hit(&ray, interval).expect("The ray hits the disk.");
+
+    assert_relative_eq!(record.t, 2.0);
+    assert!(record.front_face);
+    assert_relative_eq!(record.v, 0.5);
+    assert_relative_eq!(record.dpdu.dot(record.normal), 0.0, epsilon = 1e-5);
+    assert!(record.dpdu.cross(record.dpdv).dot(record.normal) > 0.0);
+
+    // beyond the rim
+    assert!(disk.hit(&Ray::new(Point::new(2.1, 3.0, 0.0), Point::new(0.0, -1.0, 0.0)), interval).is_none());
+
+}
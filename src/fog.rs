@@ -0,0 +1,160 @@
+use crate::light::Illuminate;
+use crate::medium::Phase;
+use crate::ray::Ray;
+use crate::sky::Sky;
+use crate::vec3::{Color, Point};
+
+use std::f32::consts::PI;
+
+//
+// Fog struct
+// scene wide exponential height fog, sigma(y) = density * exp(-falloff * (y - base_height)),
+// integrated in closed form along every ray segment, with single scattering of a constant
+// sky ambient and of an optional sun lobe; both are set by hand, or from a physical sky with
+// set_sky so that the fog matches what the camera shows around it
+#[derive(Debug, Clone, Copy)]
+pub struct Fog {
+
+    density: f32,
+    height_falloff: f32,
+    base_height: f32,
+    // radiance scattered towards the viewer by a fully opaque fog, from the whole sky
+    color: Color,
+    // direction towards the sun, its radiance scattered by the fog and the phase function of the lobe
+    sun: Option<(Point, Color, Phase)>,
+
+}
+
+impl Fog {
+
+    // the same density everywhere, the transmittance only depends on the distance
+    pub fn distance(density: f32, color: Color) -> Self {
+        Fog::height(density, 0.0, 0.0, color)
+    }
+
+    // density at base_height, thinning out by a factor e every 1/height_falloff upwards
+    pub fn height(density: f32, height_falloff: f32, base_height: f32, color: Color) -> Self {
+        Self { density, height_falloff, base_height, color, sun: None }
+    }
+
+    // adds the glow around the sun, anisotropy is the Henyey-Greenstein asymmetry of the lobe
+    pub fn set_sun(self, direction: Point, color: Color, anisotropy: f32) -> Self {
+        Self { sun: Some((Point::unit_vector(&direction), color, Phase::HenyeyGreenstein(anisotropy))), ..self }
+    }
+
+    // ambient and sun lobe scattered from the given sky and its sun, for a fog of albedo one
+    pub fn set_sky(self, sky: &Sky, anisotropy: f32) -> Self {
+
+        // mean radiance of the sky over the sphere, on a fixed grid weighted by solid angle
+        let (n_theta, n_phi) = (16, 32);
+        let mut total = Color::default();
+        let mut weight = 0.0;
+        for i in 0..n_theta {
+            let theta = PI*(i as f32 + 0.5) / n_theta as f32;
+            for j in 0..n_phi {
+                let phi = 2.0*PI*(j as f32 + 0.5) / n_phi as f32;
+                let direction = Point::new(theta.sin()*phi.cos(), theta.cos(), theta.sin()*phi.sin());
+                total = total + sky.radiance(direction)*theta.sin();
+                weight += theta.sin();
+            }
+        }
+
+        // the lobe is scaled by 4pi, an irradiance E scatters E*phase
+        let irradiance = sky.sun().illuminate(Point::default()).map_or(Color::default(), |(_, _, irradiance)| irradiance);
+
+        Self { color: total / weight, ..self }.set_sun(sky.sun_direction(), irradiance / (4.0*PI), anisotropy)
+
+    }
+
+    // optical depth of the segment from the ray origin to t (possibly infinite)
+    pub fn optical_depth(&self, ray: &Ray, t: f32) -> f32 {
+
+        if self.density <= 0.0 { return 0.0 }
+
+        let length = ray.direction().length();
+        let distance = t*length;
+        let slope = self.height_falloff * ray.direction().y() / length;
+
+        let start = self.density * (-self.height_falloff*(ray.origin().y() - self.base_height)).exp();
+
+        // integral of exp(-slope*s) over [0:distance], the limit being the distance for a level ray
+        let x = slope*distance;
+        let integral = if x.abs() < 1e-4 {
+            distance*(1.0 - 0.5*x)
+        } else if distance.is_infinite() {
+            if slope > 0.0 { 1.0 / slope } else { f32::INFINITY }
+        } else {
+            -(-x).exp_m1() / slope
+        };
+
+        return start*integral;
+
+    }
+
+    // radiance reaching the ray origin from a surface at t that sends the given radiance
+    pub fn apply(&self, ray: &Ray, t: f32, radiance: Color) -> Color {
+
+        let transmittance = (-self.optical_depth(ray, t)).exp();
+
+        if transmittance >= 1.0 { return radiance }
+
+        let mut in_scattered = self.color;
+
+        if let Some((direction, color, phase)) = self.sun {
+            let cos_theta = Point::unit_vector(&ray.direction()).dot(direction);
+            in_scattered = in_scattered + color*(4.0*PI*phase.evaluate(cos_theta));
+        }
+
+        return radiance*transmittance + ray.spectrum(in_scattered)*(1.0 - transmittance);
+
+    }
+
+}
+
+//
+// tests
+#[test]
+fn test_fog(){
+
+    let white = Color::new(1.0, 1.0, 1.0);
+    let ray = Ray::new(Point::new(0.0, 1.0, 0.0), Point::new(0.0, 0.0, -2.0));
+
+    // distance fog is Beer-Lambert over the segment length
+    let fog = Fog::distance(0.1, white);
+    assert_relative_eq!(fog.optical_depth(&ray, 5.0), 1.0, epsilon = 1e-5);
+    assert_eq!(fog.apply(&ray, f32::INFINITY, Color::default()), white);
+
+    let black = fog.apply(&ray, 5.0, Color::default());
+    assert_relative_eq!(black.x(), 1.0 - (-1.0_f32).exp(), epsilon = 1e-5);
+
+    // height fog matches a numerical integration along a tilted ray
+    let fog = Fog::height(0.5, 0.8, 0.2, white);
+    let ray = Ray::new(Point::new(0.0, 1.0, 0.0), Point::new(0.3, 0.4, -1.0));
+    let t = 3.0;
+    let n = 10000;
+    let numerical: f32 = (0..n).map(|i| {
+        let p = ray.at(t*(i as f32 + 0.5) / (n as f32));
+        0.5*(-0.8*(p.y() - 0.2)).exp()
+    }).sum::<f32>() * t*ray.direction().length() / (n as f32);
+    assert_relative_eq!(fog.optical_depth(&ray, t), numerical, epsilon = 1e-3);
+
+    // looking up through height fog the sky stays visible, looking down it does not
+    assert!(fog.optical_depth(&ray, f32::INFINITY).is_finite());
+    let down = Ray::new(Point::new(0.0, 1.0, 0.0), Point::new(0.0, -0.1, -1.0));
+    assert_eq!(fog.optical_depth(&down, f32::INFINITY), f32::INFINITY);
+
+    // the sun lobe brightens the fog towards the sun
+    let fog = Fog::distance(0.1, Color::new(0.5, 0.5, 0.5)).set_sun(Point::new(0.0, 0.0, -1.0), white, 0.7);
+    let towards = fog.apply(&Ray::new(Point::default(), Point::new(0.0, 0.0, -1.0)), 10.0, Color::default());
+    let away = fog.apply(&Ray::new(Point::default(), Point::new(0.0, 0.0, 1.0)), 10.0, Color::default());
+    assert!(towards.x() > 2.0*away.x());
+
+    // a fog under a physical sky takes its color from the sky and its glow around the sun
+    let sky = Sky::new(30.0, 90.0, 3.0);
+    let fog = Fog::distance(0.1, white).set_sky(&sky, 0.7);
+    let towards = fog.apply(&Ray::new(Point::default(), sky.sun_direction()), f32::INFINITY, Color::default());
+    let away = fog.apply(&Ray::new(Point::default(), -sky.sun_direction()), f32::INFINITY, Color::default());
+    assert!(towards.x() > 2.0*away.x());
+    assert!(away.z() > away.x());
+
+}
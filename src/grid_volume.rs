@@ -0,0 +1,111 @@
+use crate::{vec3::Point, ray::Ray, interval::Interval};
+use crate::hittable::{Hittable, HitRecord, HittableObject};
+use crate::material::{Material, Volume};
+use crate::medium::{Grid, Medium};
+
+//
+// heterogeneous medium bounded by the box of its voxel grid
+pub struct GridVolume {
+    min: Point,
+    max: Point,
+    material: Material,
+}
+
+impl GridVolume {
+
+    pub fn new(grid: Grid) -> HittableObject {
+
+        let (min, max) = grid.bounds();
+
+        HittableObject::GridVolume(Self { min, max, material: Volume::new(Medium::Grid(grid)) })
+
+    }
+
+}
+
+impl Hittable for GridVolume {
+
+    fn hit(&self, ray: &Ray, interval: Interval) -> Option<(HitRecord, &Material)> {
+
+        // slab test, keeping the axis of the entry and exit faces
+        let origin = [ray.origin().x(), ray.origin().y(), ray.origin().z()];
+        let direction = [ray.direction().x(), ray.direction().y(), ray.direction().z()];
+        let min = [self.min.x(), self.min.y(), self.min.z()];
+        let max = [self.max.x(), self.max.y(), self.max.z()];
+
+        let (mut t_enter, mut t_exit) = (f32::NEG_INFINITY, f32::INFINITY);
+        let (mut axis_enter, mut axis_exit) = (0, 0);
+
+        for axis in 0..3 {
+
+            let inverse = 1.0 / direction[axis];
+            let t0 = (min[axis] - origin[axis]) * inverse;
+            let t1 = (max[axis] - origin[axis]) * inverse;
+            let (t0, t1) = if t0 < t1 { (t0, t1) } else { (t1, t0) };
+
+            if t0 > t_enter { t_enter = t0; axis_enter = axis }
+            if t1 < t_exit { t_exit = t1; axis_exit = axis }
+
+        }
+
+        if t_enter > t_exit { return None }
+
+        let (root, axis) = if interval.surrounds(t_enter) {
+            (t_enter, axis_enter)
+        } else if interval.surrounds(t_exit) {
+            (t_exit, axis_exit)
+        } else {
+            return None
+        };
+
+        // outward normal of the face, with the surface coordinates of the other two axes
+        let hit_location = ray.at(root);
+        let center = (self.min + self.max) / 2.0;
+        let side = if [hit_location.x(), hit_location.y(), hit_location.z()][axis] > [center.x(), center.y(), center.z()][axis] { 1.0 } else { -1.0 };
+
+        let axes = [Point::new(1.0, 0.0, 0.0), Point::new(0.0, 1.0, 0.0), Point::new(0.0, 0.0, 1.0)];
+        let normal = axes[axis]*side;
+        let dpdu = axes[(axis + 1) % 3]*side;
+        let dpdv = axes[(axis + 2) % 3];
+
+        let local = (hit_location - self.min) / (self.max - self.min);
+        let local = [local.x(), local.y(), local.z()];
+        let record = HitRecord::new(hit_location, normal, root, ray, local[(axis + 1) % 3], local[(axis + 2) % 3], dpdu, dpdv);
+
+        return Some((record, &self.material));
+
+    }
+
+}
+
+//
+// tests
+#[test]
+fn test_grid_volume(){
+
+    use crate::medium::Phase;
+    use crate::vec3::Color;
+
+    let grid = Grid::new([1, 1, 1], vec![1.0], Point::new(-1.0, 0.0, -1.0), Point::new(1.0, 2.0, 1.0),
+        1.0, Color::new(1.0, 1.0, 1.0), Phase::Isotropic);
+    let volume = GridVolume::new(grid);
+    let interval = Interval::universe().set_min(0.001);
+
+    // entering through the bottom face
+    let ray = Ray::new(Point::new(0.2, -2.0, 0.1), Point::new(0.0, 1.0, 0.0));
+    let (record, material) = volume.hit(&ray, interval).expect("The ray hits the box.");
+    assert_relative_eq!(record.t, 2.0);
+    assert!(record.front_face);
+    assert_eq!(record.normal, Point::new(0.0, -1.0, 0.0));
+    assert!(material.interior().is_some());
+
+    // leaving through the top from the inside
+    let ray = Ray::new(Point::new(0.2, 1.0, 0.1), Point::new(0.0, 1.0, 0.0));
+    let (record, _) = volume.hit(&ray, interval).expect("The ray leaves the box.");
+    assert_relative_eq!(record.t, 1.0);
+    assert!(!record.front_face);
+
+    let ray = Ray::new(Point::new(3.0, 1.0, 0.0), Point::new(0.0, 1.0, 0.0));
+    assert!(volume.hit(&ray, interval).is_none());
+
+}
@@ -1,51 +1,158 @@
+use crate::light::Light;
+use crate::light_sampler::LightSampler;
 use crate::material::Material;
-use crate::{vec3::Point, ray::Ray, interval::Interval};
+use crate::{vec3::Point, ray::{Ray, RayKind}, interval::Interval};
 use crate::sphere::Sphere;
+use crate::quad::Quad;
+use crate::disk::Disk;
+use crate::cuboid::Cuboid;
+use crate::plane::Plane;
+use crate::constant_medium::ConstantMedium;
+use crate::grid_volume::GridVolume;
+
+use std::sync::OnceLock;
 
 //
 // main trait 
 pub trait Hittable {
     
-    fn hit(&self, ray: &Ray, interval: Interval) -> Option<(HitRecord, Material)>;
+    fn hit(&self, ray: &Ray, interval: Interval) -> Option<(HitRecord, &Material)>;
 
 }
 
 pub enum HittableObject {
     Sphere(Sphere),
+    Quad(Quad),
+    Disk(Disk),
+    Cuboid(Cuboid),
+    Plane(Plane),
+    ConstantMedium(ConstantMedium),
+    GridVolume(GridVolume),
 }
 
 impl Hittable for HittableObject {
 
-    fn hit(&self, ray: &Ray, interval: Interval) -> Option<(HitRecord, Material)> {
+    fn hit(&self, ray: &Ray, interval: Interval) -> Option<(HitRecord, &Material)> {
         match self {
             Self::Sphere(s) => s.hit(ray, interval),
+            Self::Quad(q) => q.hit(ray, interval),
+            Self::Disk(d) => d.hit(ray, interval),
+            Self::Cuboid(c) => c.hit(ray, interval),
+            Self::Plane(p) => p.hit(ray, interval),
+            Self::ConstantMedium(m) => m.hit(ray, interval),
+            Self::GridVolume(g) => g.hit(ray, interval),
             // Handle other hittable types here
         }
     }
 
 }
 
+impl HittableObject {
+
+    // the lights sampling the object when its material is an emitter, one per side of a box
+    pub fn area_lights(&self) -> Vec<Light> {
+        match self {
+            Self::Sphere(s) => s.area_light().into_iter().collect(),
+            Self::Quad(q) => q.area_light().into_iter().collect(),
+            Self::Disk(d) => d.area_light().into_iter().collect(),
+            Self::Cuboid(c) => c.area_lights(),
+            // an emitting plane has infinite power and is only seen by hits, media only glow
+            // inside, through the camera
+            Self::Plane(_) | Self::ConstantMedium(_) | Self::GridVolume(_) => Vec::new(),
+        }
+    }
+
+}
+
 //
 // hit record struct
 #[derive(Debug, Clone)]
 pub struct HitRecord {
     pub hit_location: Point,
     pub normal: Point,
+    pub shading_normal: Point,
     pub t: f32,
     pub front_face: bool,
+    pub u: f32,
+    pub v: f32,
+    pub dpdu: Point,
+    pub dpdv: Point,
+    // index of the object hit in its list
+    pub object: usize,
 }
 
 impl HitRecord {
     
-    pub fn new(hit_location: Point, normal: Point, t: f32, ray: &Ray) -> Self {
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(hit_location: Point, normal: Point, t: f32, ray: &Ray, u: f32, v: f32, dpdu: Point, dpdv: Point) -> Self {
 
         // normal is assumed to be normalized !!!
+        // (u, v) are the surface coordinates of the hit, and dpdu/dpdv the (unnormalized) 
+        // partial derivatives of the surface position along them, oriented with the outward normal
+        // the shading normal starts out as the geometric one, normal maps perturb it later
 
         let front_face = ray.direction().dot(normal) < 0.0;
         let normal = if front_face { normal } else { -normal };
 
-        Self { hit_location, normal, t, front_face }
+        Self { hit_location, normal, shading_normal: normal, t, front_face, u, v, dpdu, dpdv, object: 0 }
+
+    }
+
+}
+
+//
+// Visibility, the kinds of rays that see an object
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Visibility {
+    pub camera: bool,
+    // whether it blocks the light on its way to other objects
+    pub shadow: bool,
+    pub reflection: bool,
+    pub refraction: bool,
+}
+
+impl Default for Visibility {
+
+    fn default() -> Self {
+        Self { camera: true, shadow: true, reflection: true, refraction: true }
+    }
+
+}
+
+impl Visibility {
+
+    fn sees(&self, kind: RayKind) -> bool {
+        match kind {
+            RayKind::Camera => self.camera,
+            RayKind::Shadow => self.shadow,
+            RayKind::Reflection => self.reflection,
+            RayKind::Refraction => self.refraction,
+        }
+    }
+
+}
+
+//
+// LightLinks, the objects a light illuminates
+#[derive(Debug, Clone, Default)]
+pub struct LightLinks {
+    // only these objects if any, never the excluded ones
+    include: Option<Vec<usize>>,
+    exclude: Vec<usize>,
+}
+
+impl LightLinks {
 
+    pub fn only(objects: &[usize]) -> Self {
+        Self { include: Some(objects.to_vec()), exclude: Vec::new() }
+    }
+
+    pub fn except(objects: &[usize]) -> Self {
+        Self { include: None, exclude: objects.to_vec() }
+    }
+
+    pub fn illuminates(&self, object: usize) -> bool {
+        self.include.as_ref().is_none_or(|include| include.contains(&object)) && !self.exclude.contains(&object)
     }
 
 }
@@ -54,12 +161,19 @@ impl HitRecord {
 // hittable list struct
 pub struct HittableList {
     objects: Vec<HittableObject>,
+    visibility: Vec<Visibility>,
+    // lights of each emitting object
+    emitters: Vec<Vec<usize>>,
+    lights: Vec<Light>,
+    links: Vec<LightLinks>,
+    // built on first use, from the lights as they are then
+    light_sampler: OnceLock<LightSampler>,
 }
 
 impl HittableList {
     
     pub fn new() -> Self {
-        Self { objects: Vec::new() }
+        Self { objects: Vec::new(), visibility: Vec::new(), emitters: Vec::new(), lights: Vec::new(), links: Vec::new(), light_sampler: OnceLock::new() }
     }
 
     pub fn new_with_object<T>(object: HittableObject) -> Self {
@@ -70,29 +184,84 @@ impl HittableList {
 
     pub fn clear(&mut self) {
         self.objects.clear();
+        self.visibility.clear();
+        self.emitters.clear();
+        self.lights.clear();
+        self.links.clear();
+        self.light_sampler = OnceLock::new();
     }
 
-    pub fn add(&mut self, object: HittableObject) {
+    // index of the object, for its visibility and the light links;
+    // emitters are also added to the lights, they only light the scene through them
+    pub fn add(&mut self, object: HittableObject) -> usize {
+        let emitter = object.area_lights().into_iter().map(|light| self.add_light(light)).collect();
         self.objects.push(object);
+        self.visibility.push(Visibility::default());
+        self.emitters.push(emitter);
+        self.objects.len() - 1
+    }
+
+    // index of the light, for its links
+    pub fn add_light(&mut self, light: Light) -> usize {
+        self.lights.push(light);
+        self.links.push(LightLinks::default());
+        self.light_sampler = OnceLock::new();
+        self.lights.len() - 1
+    }
+
+    pub fn set_visibility(&mut self, object: usize, visibility: Visibility) {
+        self.visibility[object] = visibility;
+    }
+
+    pub fn set_links(&mut self, light: usize, links: LightLinks) {
+        self.links[light] = links;
+    }
+
+    pub fn lights(&self) -> &[Light] {
+        &self.lights
+    }
+
+    pub fn light_sampler(&self) -> &LightSampler {
+        self.light_sampler.get_or_init(|| LightSampler::new(&self.lights))
+    }
+
+    // the lights added for an emitting object, none for the others
+    pub fn emitter_lights(&self, object: usize) -> &[usize] {
+        &self.emitters[object]
+    }
+
+    pub fn illuminates(&self, light: usize, object: usize) -> bool {
+        self.links[light].illuminates(object)
     }
 
 }
 
 impl Hittable for HittableList {
 
-    fn hit(&self, ray: &Ray, interval: Interval) -> Option<(HitRecord, Material)> {
+    fn hit(&self, ray: &Ray, interval: Interval) -> Option<(HitRecord, &Material)> {
         
         let mut closest_so_far = interval.max();
         let mut hit_anything = None;
 
-        for object in &self.objects {
+        for (index, object) in self.objects.iter().enumerate() {
+
+            if !self.visibility[index].sees(ray.kind()) { continue }
+
+            let mut interval = interval.set_max(closest_so_far);
 
-            let interval = interval.set_max(closest_so_far);
+            while let Some((hit, material)) = object.hit(ray, interval) {
 
-            if let Some((hit, material)) = object.hit(ray, interval) {
+                // transparent texels are misses, look for the next hit of the same object
+                let opacity = material.opacity(&hit);
+
+                if opacity < 1.0 && opacity <= Point::random_float() {
+                    interval = interval.set_min(hit.t);
+                    continue;
+                }
 
                 closest_so_far = hit.t;
-                hit_anything = Some((hit, material));
+                hit_anything = Some((HitRecord { object: index, ..hit }, material));
+                break;
                 
             }
 
@@ -103,3 +272,95 @@ impl Hittable for HittableList {
     }
     
 }
+
+impl Default for HittableList {
+    
+    fn default() -> Self {
+        Self::new()
+    }
+
+}
+
+//
+// tests
+#[test]
+fn test_cutout(){
+
+    use crate::material::{Cutout, Lambertian};
+    use crate::sphere::Sphere;
+    use crate::texture::Image;
+    use crate::vec3::Color;
+
+    let grey = Lambertian::new(Color::new(0.5, 0.5, 0.5));
+    let interval = Interval::universe().set_min(0.001);
+    let ray = Ray::new(Point::new(0.0, 0.0, 5.0), Point::new(0.0, 0.0, -1.0));
+
+    // a fully transparent sphere in front of an opaque one is skipped, both of its sides
+    let mut world = HittableList::new();
+    world.add(Sphere::new(Point::new(0.0, 0.0, 2.0), 1.0, Cutout::new(grey.clone(), 0.0)));
+    world.add(Sphere::new(Point::new(0.0, 0.0, -2.0), 1.0, grey.clone()));
+
+    let (record, _) = world.hit(&ray, interval).expect("The ray hits the opaque sphere.");
+    assert_relative_eq!(record.t, 6.0);
+
+    // a mask opaque on one half only, the hit comes from the back side of the sphere here
+    let mask = Image::new(2, 1, vec![Color::new(0.0, 0.0, 0.0), Color::new(1.0, 1.0, 1.0)]);
+    let mut world = HittableList::new();
+    world.add(Sphere::new(Point::default(), 1.0, Cutout::textured(grey.clone(), mask)));
+
+    let (record, _) = world.hit(&ray, interval).expect("The ray hits the opaque half.");
+    assert!(!record.front_face);
+
+    // half opacity stops half of the rays at the first hit
+    let mut world = HittableList::new();
+    world.add(Sphere::new(Point::default(), 1.0, Cutout::new(grey, 0.5)));
+
+    let n = 10000;
+    let first = (0..n).filter(|_| world.hit(&ray, interval).is_some_and(|(record, _)| record.front_face)).count();
+    assert_relative_eq!(first as f32 / n as f32, 0.5, epsilon = 0.03);
+
+}
+
+#[test]
+fn test_visibility(){
+
+    use crate::material::{DiffuseLight, Lambertian};
+    use crate::sphere::Sphere;
+    use crate::vec3::Color;
+
+    let grey = Lambertian::new(Color::new(0.5, 0.5, 0.5));
+    let interval = Interval::universe().set_min(0.001);
+    let ray = Ray::new(Point::new(0.0, 0.0, 5.0), Point::new(0.0, 0.0, -1.0));
+
+    let mut world = HittableList::new();
+    let front = world.add(Sphere::new(Point::new(0.0, 0.0, 2.0), 1.0, grey.clone()));
+    let back = world.add(Sphere::new(Point::new(0.0, 0.0, -2.0), 1.0, grey));
+    let (record, _) = world.hit(&ray, interval).expect("The ray hits the front sphere.");
+    assert_eq!(record.object, front);
+
+    // hidden from the camera only, still casting shadows and reflected
+    world.set_visibility(front, Visibility { camera: false, ..Visibility::default() });
+    let (record, _) = world.hit(&ray, interval).expect("The ray hits the back sphere.");
+    assert_eq!(record.object, back);
+    assert!(world.hit(&ray.clone().set_kind(RayKind::Shadow), interval).is_some_and(|(record, _)| record.object == front));
+    assert!(world.hit(&ray.clone().set_kind(RayKind::Reflection), interval).is_some_and(|(record, _)| record.object == front));
+
+    world.set_visibility(back, Visibility { shadow: false, refraction: false, ..Visibility::default() });
+    world.set_visibility(front, Visibility { shadow: false, ..Visibility::default() });
+    assert!(world.hit(&ray.clone().set_kind(RayKind::Shadow), interval).is_none());
+    assert!(world.hit(&ray.clone().set_kind(RayKind::Refraction), interval).is_some_and(|(record, _)| record.object == front));
+
+    // emitters get their light, linked to every object unless told otherwise
+    let lamp = world.add(Sphere::new(Point::new(0.0, 3.0, 0.0), 0.5, DiffuseLight::new(Color::new(1.0, 1.0, 1.0))));
+    assert_eq!(world.emitter_lights(lamp).len(), 1);
+    let light = world.emitter_lights(lamp)[0];
+    assert!(world.emitter_lights(front).is_empty());
+    assert!(world.illuminates(light, front) && world.illuminates(light, back));
+
+    world.set_links(light, LightLinks::except(&[front]));
+    assert!(!world.illuminates(light, front) && world.illuminates(light, back));
+
+    world.set_links(light, LightLinks::only(&[front]));
+    assert!(world.illuminates(light, front) && !world.illuminates(light, back));
+
+}
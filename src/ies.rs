@@ -0,0 +1,218 @@
+use crate::vec3::Point;
+
+use std::f32::consts::PI;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+//
+// IesProfile, the candela distribution of a luminaire read from an IES LM-63 file
+#[derive(Debug, Clone)]
+pub struct IesProfile {
+
+    // type C angles in degrees: vertical from the nadir (0) to the zenith (180), horizontal
+    // counterclockwise from the luminaire's x-axis seen from above
+    vertical: Vec<f32>,
+    horizontal: Vec<f32>,
+    // one row of vertical samples per horizontal angle, scaled to a peak of 1
+    candela: Vec<f32>,
+    // peak intensity of the file in candela, multipliers included
+    peak: f32,
+    // mean of the scaled distribution over the sphere of directions
+    average: f32,
+
+}
+
+impl IesProfile {
+
+    pub fn load<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+
+        // LM-63 files are plain text, but not always utf-8
+        let bytes = fs::read(path)?;
+
+        return IesProfile::parse(&String::from_utf8_lossy(&bytes));
+
+    }
+
+    pub fn parse(text: &str) -> io::Result<Self> {
+
+        let invalid = |msg: &str| io::Error::new(io::ErrorKind::InvalidData, msg.to_string());
+
+        // the header (version line and [KEYWORD] lines) ends with the TILT line
+        let mut lines = text.lines();
+        let tilt = lines.by_ref()
+            .map(str::trim)
+            .find(|line| line.starts_with("TILT"))
+            .ok_or_else(|| invalid("Missing TILT line in IES file."))?;
+
+        // everything after it is numbers, separated by blanks or commas and wrapped freely
+        let mut numbers = lines
+            .flat_map(|line| line.split(|c: char| c.is_whitespace() || c == ',').map(str::to_owned).collect::<Vec<_>>())
+            .filter(|s| !s.is_empty())
+            .map(|s| s.parse::<f32>().map_err(|_| invalid("Invalid number in IES file.")));
+        let mut next = || numbers.next().unwrap_or_else(|| Err(invalid("Truncated IES file.")));
+
+        // lamp tilt factors only matter for lamps that are not mounted as measured, they are skipped
+        if tilt.trim_start_matches("TILT").trim_start_matches([' ', '=']) == "INCLUDE" {
+            next()?;
+            let count = (next()? as usize).checked_mul(2).ok_or_else(|| invalid("Invalid TILT data in IES file."))?;
+            for _ in 0..count { next()?; }
+        }
+
+        // lamp count, lumens per lamp, candela multiplier, angle counts, photometric type,
+        // units and dimensions, then ballast factor, ballast-lamp factor and input watts
+        let mut header = [0.0; 13];
+        for value in header.iter_mut() { *value = next()?; }
+
+        let multiplier = header[2]*header[10]*header[11];
+        let (vertical_count, horizontal_count) = (header[3] as usize, header[4] as usize);
+
+        if header[5] as i32 != 1 { return Err(invalid("Only type C photometry is supported.")) }
+        if vertical_count == 0 || horizontal_count == 0 { return Err(invalid("IES file without angles.")) }
+
+        let vertical = (0..vertical_count).map(|_| next()).collect::<io::Result<Vec<f32>>>()?;
+        let horizontal = (0..horizontal_count).map(|_| next()).collect::<io::Result<Vec<f32>>>()?;
+        let samples = vertical_count.checked_mul(horizontal_count).ok_or_else(|| invalid("Too many angles in IES file."))?;
+        let candela = (0..samples).map(|_| next().map(|c| c*multiplier)).collect::<io::Result<Vec<f32>>>()?;
+
+        if vertical.windows(2).any(|w| w[0] >= w[1]) || horizontal.windows(2).any(|w| w[0] >= w[1]) {
+            return Err(invalid("IES angles are not increasing."));
+        }
+
+        let peak = candela.iter().cloned().fold(0.0, f32::max);
+
+        if peak <= 0.0 { return Err(invalid("IES file without any light.")) }
+
+        let candela = candela.iter().map(|c| c.max(0.0) / peak).collect();
+        let mut profile = Self { vertical, horizontal, candela, peak, average: 0.0 };
+
+        // midpoint rule over the sphere, fine enough for the light tree
+        let (n_theta, n_phi) = (90, 180);
+        let mut total = 0.0;
+        for i in 0..n_theta {
+            let theta = (i as f32 + 0.5) / n_theta as f32 * 180.0;
+            for j in 0..n_phi {
+                let phi = (j as f32 + 0.5) / n_phi as f32 * 360.0;
+                total += profile.value(theta, phi)*theta.to_radians().sin();
+            }
+        }
+        profile.average = total*(PI / n_theta as f32)*(2.0*PI / n_phi as f32) / (4.0*PI);
+
+        return Ok(profile);
+
+    }
+
+    pub fn peak(&self) -> f32 {
+        self.peak
+    }
+
+    pub fn average(&self) -> f32 {
+        self.average
+    }
+
+    // scaled intensity towards a direction given in the photometric frame: z is the nadir,
+    // x the horizontal angle 0 and y the horizontal angle 270 (clockwise from above)
+    pub fn intensity(&self, direction: Point) -> f32 {
+
+        let theta = direction.z().clamp(-1.0, 1.0).acos().to_degrees();
+        let phi = (-direction.y()).atan2(direction.x()).to_degrees();
+
+        return self.value(theta, if phi < 0.0 { phi + 360.0 } else { phi });
+
+    }
+
+    // scaled intensity at the angles in degrees, vertical in [0:180] and horizontal in [0:360)
+    fn value(&self, theta: f32, phi: f32) -> f32 {
+
+        let (first, last) = (self.horizontal[0], self.horizontal[self.horizontal.len() - 1]);
+
+        // the horizontal angles given cover the rest by symmetry
+        let phi = if self.horizontal.len() == 1 {
+            first
+        } else if first == 0.0 && last == 90.0 {
+            let phi = if phi > 180.0 { 360.0 - phi } else { phi };
+            if phi > 90.0 { 180.0 - phi } else { phi }
+        } else if first == 0.0 && last == 180.0 {
+            if phi > 180.0 { 360.0 - phi } else { phi }
+        } else if first == 90.0 && last == 270.0 {
+            if phi < 90.0 { 180.0 - phi } else if phi > 270.0 { 540.0 - phi } else { phi }
+        } else { phi };
+
+        let row = |h: usize| -> f32 {
+            let Some((v, t)) = locate(&self.vertical, theta) else { return 0.0 };
+            let samples = &self.candela[h*self.vertical.len()..];
+            return samples[v]*(1.0 - t) + samples[(v + 1).min(self.vertical.len() - 1)]*t;
+        };
+
+        let Some((h, t)) = locate(&self.horizontal, phi) else { return 0.0 };
+
+        return row(h)*(1.0 - t) + row((h + 1).min(self.horizontal.len() - 1))*t;
+
+    }
+
+}
+
+// index of the interval of the sorted angles holding x, and the fraction along it
+fn locate(angles: &[f32], x: f32) -> Option<(usize, f32)> {
+
+    let (first, last) = (angles[0], angles[angles.len() - 1]);
+
+    if angles.len() == 1 { return Some((0, 0.0)) }
+    if x < first - 1e-3 || x > last + 1e-3 { return None }
+
+    let i = angles.partition_point(|&a| a <= x).clamp(1, angles.len() - 1) - 1;
+    let t = ((x - angles[i]) / (angles[i + 1] - angles[i])).clamp(0.0, 1.0);
+
+    return Some((i, t));
+
+}
+
+//
+// tests
+#[test]
+fn test_downlight(){
+
+    let path = concat!(env!("CARGO_MANIFEST_DIR"), "/media/ies/downlight.ies");
+    let profile = IesProfile::load(path).expect("Valid IES file.");
+
+    assert_relative_eq!(profile.peak(), 1000.0);
+
+    // straight down, interpolated between the samples, nothing upwards
+    let down = Point::new(0.0, 0.0, 1.0);
+    assert_relative_eq!(profile.intensity(down), 1.0);
+    assert_relative_eq!(profile.value(25.0, 0.0), 0.68);
+    assert_relative_eq!(profile.value(120.0, 0.0), 0.0);
+
+    // rotationally symmetric
+    let tilted = Point::unit_vector(&Point::new(1.0, 0.0, 1.0));
+    let turned = Point::unit_vector(&Point::new(0.0, -1.0, 1.0));
+    assert_relative_eq!(profile.intensity(tilted), profile.intensity(turned), epsilon = 1e-5);
+    assert_relative_eq!(profile.intensity(tilted), 0.3 + (0.12 - 0.3)*0.5, epsilon = 1e-4);
+
+    // all the light in the lower hemisphere, less than an isotropic source of the same peak
+    assert!(profile.average() > 0.05 && profile.average() < 0.5);
+
+}
+
+#[test]
+fn test_wallwash(){
+
+    let profile = IesProfile::parse(include_str!("../media/ies/wallwash.ies")).expect("Valid IES file.");
+
+    // candela multiplier, tilt data skipped, values wrapped over lines
+    assert_relative_eq!(profile.peak(), 600.0);
+    assert_relative_eq!(profile.value(45.0, 0.0), 1.0);
+    assert_relative_eq!(profile.value(45.0, 90.0), 0.6);
+    assert_relative_eq!(profile.value(45.0, 135.0), (0.6 + 1.0/3.0) / 2.0);
+
+    // bilateral symmetry about the 0-180 plane
+    assert_relative_eq!(profile.value(45.0, 270.0), profile.value(45.0, 90.0));
+    assert_relative_eq!(profile.value(90.0, 300.0), profile.value(90.0, 60.0));
+
+    // only type C, and complete files
+    assert!(IesProfile::parse("TILT=NONE\n1 1000 1 1 1 2 2 0 0 0\n1 1 1\n0\n0\n100\n").is_err());
+    assert!(IesProfile::parse("TILT=NONE\n1 1000 1 2 1 1 2 0 0 0\n1 1 1\n0 90\n0\n100\n").is_err());
+    assert!(IesProfile::parse("1 1000 1 1 1 1 2 0 0 0\n").is_err());
+    assert!(IesProfile::parse("TILT=INCLUDE\n1 1e30\n").is_err());
+
+}
@@ -42,15 +42,16 @@ impl Interval {
     }
 
     pub fn set_min(self, min: f32) -> Self {
-        Self { min: min, max: self.max }
+        Self { min, max: self.max }
     }
 
     pub fn set_max(self, max: f32) -> Self {
-        Self { min: self.min, max: max }
+        Self { min: self.min, max }
     }
 }
 
 #[test]
+#[allow(clippy::bool_assert_comparison)]
 fn test_contains(){
     
     let interval = Interval::universe().set_min(-1.0).set_max(2.0);
@@ -65,6 +66,7 @@ fn test_contains(){
 }
 
 #[test]
+#[allow(clippy::bool_assert_comparison)]
 fn test_surrounds(){
     
     let interval = Interval::universe().set_min(-1.0).set_max(2.0);
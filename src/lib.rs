@@ -5,7 +5,25 @@ pub mod vec3;
 pub mod ray;
 pub mod hittable;
 pub mod sphere;
+pub mod quad;
+pub mod disk;
+pub mod cuboid;
+pub mod plane;
 pub mod interval;
 pub mod camera;
 pub mod material;
 pub mod scenes;
+pub mod onb;
+pub mod texture;
+pub mod normal_map;
+pub mod noise;
+pub mod microfacet;
+pub mod spectrum;
+pub mod medium;
+pub mod constant_medium;
+pub mod grid_volume;
+pub mod fog;
+pub mod light;
+pub mod light_sampler;
+pub mod ies;
+pub mod sky;
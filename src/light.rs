@@ -0,0 +1,581 @@
+use crate::camera::degrees_to_radians;
+use crate::ies::IesProfile;
+use crate::light_sampler::{power, LightBounds};
+use crate::onb::Onb;
+use crate::vec3::{Color, Point};
+
+use std::f32::consts::PI;
+use std::sync::Arc;
+
+//
+// main trait
+pub trait Illuminate {
+
+    // unit direction from the point towards the light, distance to it, and the light arriving
+    // from it (the irradiance on a surface facing it, estimated over the directions of area
+    // lights), None if the point is not lit at all
+    fn illuminate(&self, point: Point) -> Option<(Point, f32, Color)>;
+
+}
+
+// light sources found from the surfaces by shadow rays: the point, spot and directional
+// ones are never hit by rays, the emitters of area lights only after specular bounces
+#[derive(Debug, Clone)]
+pub enum Light {
+    Point(PointLight),
+    Spot(SpotLight),
+    Directional(DirectionalLight),
+    Area(AreaLight),
+}
+
+impl Illuminate for Light {
+
+    fn illuminate(&self, point: Point) -> Option<(Point, f32, Color)> {
+        match self {
+            Self::Point(p) => p.illuminate(point),
+            Self::Spot(s) => s.illuminate(point),
+            Self::Directional(d) => d.illuminate(point),
+            Self::Area(a) => a.illuminate(point),
+            // Handle other lights here
+        }
+    }
+
+}
+
+impl Light {
+
+    // decay with the distance of point and spot lights, the others ignore it
+    pub fn set_falloff(self, falloff: Falloff) -> Self {
+        match self {
+            Self::Point(p) => Self::Point(PointLight { falloff, ..p }),
+            Self::Spot(s) => Self::Spot(SpotLight { falloff, ..s }),
+            _ => self,
+        }
+    }
+
+    // candela distribution of point and spot lights, the others ignore it: the intensity becomes
+    // the peak of the profile, whose nadir points down for point lights and along spot lights
+    pub fn set_profile(self, profile: IesProfile) -> Self {
+        let profile = Arc::new(profile);
+        match self {
+            Self::Point(p) => Self::Point(PointLight { profile: Some(Photometry::new(profile, Point::new(0.0, -1.0, 0.0))), ..p }),
+            Self::Spot(s) => Self::Spot(SpotLight { profile: Some(Photometry::new(profile, s.direction)), ..s }),
+            _ => self,
+        }
+    }
+
+    // extent, power and emission cone for the light tree, None for the infinitely far lights
+    pub fn bounds(&self) -> Option<LightBounds> {
+
+        let up = Point::new(0.0, 0.0, 1.0);
+
+        match self {
+            Self::Point(p) => {
+                let average = p.profile.as_ref().map_or(1.0, |profile| profile.profile.average());
+                Some(LightBounds::new(p.position, p.position, 4.0*PI*power(p.intensity)*average, up, -1.0, 0.0))
+            },
+            Self::Spot(s) => {
+                // full power below the inner cone, fading out up to the outer one
+                let cos_theta_e = (s.cos_outer.acos() - s.cos_inner.acos()).cos();
+                let average = s.profile.as_ref().map_or(1.0, |profile| profile.profile.average());
+                Some(LightBounds::new(s.position, s.position, 4.0*PI*power(s.intensity)*average, s.direction, s.cos_inner, cos_theta_e))
+            },
+            Self::Directional(_) => None,
+            Self::Area(a) => {
+                let phi = PI*power(a.radiance);
+                match a.shape {
+                    Shape::Sphere(center, radius) => {
+                        let r = Point::new(radius, radius, radius);
+                        Some(LightBounds::new(center - r, center + r, phi*4.0*PI*radius*radius, up, -1.0, 0.0))
+                    },
+                    Shape::Quad(corner, u, v) => {
+                        let corners = [corner, corner + u, corner + v, corner + u + v];
+                        let min = corners.iter().fold(corner, |m, c| Point::new(m.x().min(c.x()), m.y().min(c.y()), m.z().min(c.z())));
+                        let max = corners.iter().fold(corner, |m, c| Point::new(m.x().max(c.x()), m.y().max(c.y()), m.z().max(c.z())));
+                        Some(LightBounds::new(min, max, phi*u.cross(v).length(), u.cross(v), 1.0, 0.0))
+                    },
+                    Shape::Disk(center, normal, radius) => {
+                        // half extent along each axis of a tilted disk
+                        let extent = |n: f32| radius*(1.0 - n*n).max(0.0).sqrt();
+                        let r = Point::new(extent(normal.x()), extent(normal.y()), extent(normal.z()));
+                        Some(LightBounds::new(center - r, center + r, phi*PI*radius*radius, normal, 1.0, 0.0))
+                    },
+                }
+            },
+        }
+
+    }
+
+}
+
+//
+// Falloff
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Falloff {
+    // inverse square law, the physical one
+    Quadratic,
+    // inverse distance, reaches further for stylised lighting
+    Linear,
+    // no decay
+    Constant,
+}
+
+impl Falloff {
+
+    fn attenuation(&self, distance: f32) -> f32 {
+        match self {
+            Self::Quadratic => 1.0 / (distance*distance).max(1e-8),
+            Self::Linear => 1.0 / distance.max(1e-4),
+            Self::Constant => 1.0,
+        }
+    }
+
+}
+
+//
+// Photometry, a measured profile oriented in the scene
+#[derive(Debug, Clone)]
+struct Photometry {
+
+    profile: Arc<IesProfile>,
+    // w is the nadir of the profile, u its horizontal angle 0 (towards +x when possible)
+    frame: Onb,
+
+}
+
+impl Photometry {
+
+    fn new(profile: Arc<IesProfile>, nadir: Point) -> Self {
+        Self { profile, frame: Onb::from_tangent(Point::unit_vector(&nadir), Point::new(1.0, 0.0, 0.0)) }
+    }
+
+    // scaled intensity towards direction, leaving the light
+    fn factor(&self, direction: Point) -> f32 {
+        self.profile.intensity(self.frame.to_local(direction))
+    }
+
+}
+
+//
+// PointLight (point emitter, isotropic unless given a profile)
+#[derive(Debug, Clone)]
+pub struct PointLight {
+
+    position: Point,
+    // radiant intensity, per unit solid angle
+    intensity: Color,
+    falloff: Falloff,
+    profile: Option<Photometry>,
+
+}
+
+impl PointLight {
+
+    pub fn new(position: Point, intensity: Color) -> Light {
+        Light::Point(Self { position, intensity, falloff: Falloff::Quadratic, profile: None })
+    }
+
+    fn illuminate(&self, point: Point) -> Option<(Point, f32, Color)> {
+
+        let to_light = self.position - point;
+        let distance = to_light.length();
+
+        if distance <= 0.0 { return None }
+
+        let direction = to_light / distance;
+        let profile = self.profile.as_ref().map_or(1.0, |profile| profile.factor(-direction));
+
+        if profile <= 0.0 { return None }
+
+        return Some((direction, distance, self.intensity*(profile*self.falloff.attenuation(distance))));
+
+    }
+
+}
+
+//
+// SpotLight (point emitter restricted to a cone)
+#[derive(Debug, Clone)]
+pub struct SpotLight {
+
+    position: Point,
+    direction: Point,
+    intensity: Color,
+    falloff: Falloff,
+    // cosines of the half angles where the edge starts and ends
+    cos_inner: f32,
+    cos_outer: f32,
+    profile: Option<Photometry>,
+
+}
+
+impl SpotLight {
+
+    // cone_angle is the full opening in degrees, edge in [0:1] the fraction of it over which
+    // the light fades out smoothly, 0 is a hard edge
+    pub fn new(position: Point, look_at: Point, intensity: Color, cone_angle: f32, edge: f32) -> Light {
+
+        let outer = degrees_to_radians(cone_angle.clamp(0.0, 180.0)) / 2.0;
+        let inner = outer*(1.0 - edge.clamp(0.0, 1.0));
+
+        let direction = Point::unit_vector(&(look_at - position));
+
+        Light::Spot(Self { position, direction, intensity, falloff: Falloff::Quadratic, cos_inner: inner.cos(), cos_outer: outer.cos(), profile: None })
+
+    }
+
+    fn illuminate(&self, point: Point) -> Option<(Point, f32, Color)> {
+
+        let to_light = self.position - point;
+        let distance = to_light.length();
+
+        if distance <= 0.0 { return None }
+
+        let direction = to_light / distance;
+        let cosine = -direction.dot(self.direction);
+
+        if cosine <= self.cos_outer { return None }
+
+        // smoothstep across the edge
+        let x = if self.cos_inner > self.cos_outer {
+            ((cosine - self.cos_outer) / (self.cos_inner - self.cos_outer)).min(1.0)
+        } else { 1.0 };
+        let edge = x*x*(3.0 - 2.0*x)*self.profile.as_ref().map_or(1.0, |profile| profile.factor(-direction));
+
+        if edge <= 0.0 { return None }
+
+        return Some((direction, distance, self.intensity*(edge*self.falloff.attenuation(distance))));
+
+    }
+
+}
+
+//
+// DirectionalLight (distant light such as the sun)
+#[derive(Debug, Clone)]
+pub struct DirectionalLight {
+
+    direction: Point,
+    // irradiance on a surface facing the light
+    irradiance: Color,
+    // cosine of the half angular diameter, 1 for a perfectly parallel light
+    cos_max: f32,
+
+}
+
+impl DirectionalLight {
+
+    // direction points towards the light, the angular diameter in degrees (0.53 for the sun)
+    // spreads the directions over a cone and softens the shadows
+    pub fn new(direction: Point, irradiance: Color, angular_diameter: f32) -> Light {
+
+        let cos_max = (degrees_to_radians(angular_diameter.clamp(0.0, 180.0)) / 2.0).cos();
+
+        Light::Directional(Self { direction: Point::unit_vector(&direction), irradiance, cos_max })
+
+    }
+
+    fn illuminate(&self, _point: Point) -> Option<(Point, f32, Color)> {
+
+        if self.cos_max >= 1.0 { return Some((self.direction, f32::INFINITY, self.irradiance)) }
+
+        // uniform over the cone of the disk
+        let cos_theta = 1.0 - Point::random_float()*(1.0 - self.cos_max);
+        let sin_theta = (1.0 - cos_theta*cos_theta).max(0.0).sqrt();
+        let phi = 2.0*PI*Point::random_float();
+
+        let local = Point::new(sin_theta*phi.cos(), sin_theta*phi.sin(), cos_theta);
+        let direction = Onb::new(self.direction).to_world(local);
+
+        return Some((Point::unit_vector(&direction), f32::INFINITY, self.irradiance));
+
+    }
+
+}
+
+//
+// AreaLight (emitting shape sampled by solid angle)
+#[derive(Debug, Clone)]
+enum Shape {
+    // center and radius
+    Sphere(Point, f32),
+    // corner and edges, facing u x v
+    Quad(Point, Point, Point),
+    // center, unit normal and radius
+    Disk(Point, Point, f32),
+}
+
+#[derive(Debug, Clone)]
+pub struct AreaLight {
+
+    shape: Shape,
+    // emitted on the outer side only
+    radiance: Color,
+
+}
+
+impl AreaLight {
+
+    // the shapes add themselves with these when their material is a DiffuseLight
+    pub fn sphere(center: Point, radius: f32, radiance: Color) -> Light {
+        Light::Area(Self { shape: Shape::Sphere(center, radius), radiance })
+    }
+
+    pub fn quad(corner: Point, u: Point, v: Point, radiance: Color) -> Light {
+        Light::Area(Self { shape: Shape::Quad(corner, u, v), radiance })
+    }
+
+    pub fn disk(center: Point, normal: Point, radius: f32, radiance: Color) -> Light {
+        Light::Area(Self { shape: Shape::Disk(center, Point::unit_vector(&normal), radius), radiance })
+    }
+
+    fn illuminate(&self, point: Point) -> Option<(Point, f32, Color)> {
+
+        // direction, distance and pdf per unit solid angle
+        let (direction, distance, pdf) = match self.shape {
+            Shape::Sphere(center, radius) => AreaLight::sample_sphere(point, center, radius)?,
+            Shape::Quad(corner, u, v) => AreaLight::sample_quad(point, corner, u, v)?,
+            Shape::Disk(center, normal, radius) => {
+                let frame = Onb::new(normal);
+                let (r, phi) = (radius*Point::random_float().sqrt(), 2.0*PI*Point::random_float());
+                let sample = center + frame.to_world(Point::new(r*phi.cos(), r*phi.sin(), 0.0));
+                AreaLight::from_area(point, sample, normal, PI*radius*radius)?
+            }
+        };
+
+        if !(pdf > 0.0 && pdf.is_finite()) { return None }
+
+        return Some((direction, distance, self.radiance / pdf));
+
+    }
+
+    // uniform over the cone of directions the sphere covers, only lit from outside
+    fn sample_sphere(point: Point, center: Point, radius: f32) -> Option<(Point, f32, f32)> {
+
+        let to_center = center - point;
+        let distance_square = to_center.length_square();
+
+        if distance_square <= radius*radius { return None }
+
+        let distance = distance_square.sqrt();
+
+        // 1 - cos(theta_max) through the sine, which keeps its precision for small and far spheres
+        let sin_square_max = radius*radius / distance_square;
+        let cos_max = (1.0 - sin_square_max).max(0.0).sqrt();
+        let one_minus_cos_max = sin_square_max / (1.0 + cos_max);
+
+        let one_minus_cos = Point::random_float()*one_minus_cos_max;
+        let cos_theta = 1.0 - one_minus_cos;
+        let sin_theta = (one_minus_cos*(2.0 - one_minus_cos)).max(0.0).sqrt();
+        let phi = 2.0*PI*Point::random_float();
+
+        let local = Point::new(sin_theta*phi.cos(), sin_theta*phi.sin(), cos_theta);
+        let direction = Point::unit_vector(&Onb::new(to_center / distance).to_world(local));
+
+        // nearest intersection along the direction
+        let along = distance*cos_theta;
+        let across_square = (distance*sin_theta).powi(2);
+        let length = along - (radius*radius - across_square).max(0.0).sqrt();
+
+        return Some((direction, length, 1.0 / (2.0*PI*one_minus_cos_max)));
+
+    }
+
+    // uniform over the spherical rectangle (Urena et al. 2013), falls back to area sampling
+    // for skewed parallelograms and for rectangles too small to be measured reliably
+    fn sample_quad(point: Point, corner: Point, u: Point, v: Point) -> Option<(Point, f32, f32)> {
+
+        let normal = Point::unit_vector(&u.cross(v));
+
+        if normal.dot(point - corner) <= 0.0 { return None }
+
+        let (u1, u2) = (Point::random_float(), Point::random_float());
+
+        let area_sample = || {
+            let sample = corner + u*u1 + v*u2;
+            AreaLight::from_area(point, sample, normal, u.cross(v).length())
+        };
+
+        if u.dot(v).abs() > 1e-4*u.length()*v.length() { return area_sample() }
+
+        // local frame of the rectangle, the point is on the side of +z so z0 < 0
+        let (ex, ey, z) = (Point::unit_vector(&u), Point::unit_vector(&v), normal);
+        let d = corner - point;
+        let (x0, y0, z0) = (d.dot(ex), d.dot(ey), d.dot(z));
+        let (x1, y1) = (x0 + u.length(), y0 + v.length());
+
+        // normals of the planes through the point and the edges, and the interior angles
+        let (v00, v01, v10, v11) = (Point::new(x0, y0, z0), Point::new(x0, y1, z0), Point::new(x1, y0, z0), Point::new(x1, y1, z0));
+        let n0 = Point::unit_vector(&v00.cross(v10));
+        let n1 = Point::unit_vector(&v10.cross(v11));
+        let n2 = Point::unit_vector(&v11.cross(v01));
+        let n3 = Point::unit_vector(&v01.cross(v00));
+
+        let angle = |a: Point, b: Point| (-a.dot(b)).clamp(-1.0, 1.0).acos();
+        let (g0, g1, g2, g3) = (angle(n0, n1), angle(n1, n2), angle(n2, n3), angle(n3, n0));
+
+        let k = 2.0*PI - g2 - g3;
+        let solid_angle = g0 + g1 - k;
+
+        if solid_angle < 1e-4 { return area_sample() }
+
+        // x from the solid angle of the slice, then y uniformly in the projected height
+        let (b0, b1) = (n0.z(), n2.z());
+        let au = u1*solid_angle + k;
+        let fu = (au.cos()*b0 - b1) / au.sin();
+        let cu = (1.0 / (fu*fu + b0*b0).sqrt()).copysign(fu).clamp(-1.0, 1.0);
+        let xu = (-(cu*z0) / (1.0 - cu*cu).max(1e-12).sqrt()).clamp(x0, x1);
+
+        let dd = (xu*xu + z0*z0).sqrt();
+        let h0 = y0 / (dd*dd + y0*y0).sqrt();
+        let h1 = y1 / (dd*dd + y1*y1).sqrt();
+        let hv = h0 + u2*(h1 - h0);
+        let yv = if hv*hv < 1.0 - 1e-6 { hv*dd / (1.0 - hv*hv).sqrt() } else { y1 };
+
+        let offset = ex*xu + ey*yv + z*z0;
+        let distance = offset.length();
+
+        return Some((offset / distance, distance, 1.0 / solid_angle));
+
+    }
+
+    // a point picked uniformly on a flat emitter, with the pdf converted to solid angle
+    fn from_area(point: Point, sample: Point, normal: Point, area: f32) -> Option<(Point, f32, f32)> {
+
+        let offset = sample - point;
+        let distance = offset.length();
+        let direction = offset / distance;
+        let cosine = -direction.dot(normal);
+
+        if cosine <= 0.0 { return None }
+
+        return Some((direction, distance, distance*distance / (cosine*area)));
+
+    }
+
+}
+
+//
+// tests
+#[test]
+fn test_point_light(){
+
+    let white = Color::new(1.0, 1.0, 1.0);
+    let light = PointLight::new(Point::new(0.0, 4.0, 0.0), white*8.0);
+
+    let (direction, distance, irradiance) = light.illuminate(Point::new(0.0, 2.0, 0.0)).expect("Point lights light everything.");
+    assert_eq!(direction, Point::new(0.0, 1.0, 0.0));
+    assert_relative_eq!(distance, 2.0);
+    assert_relative_eq!(irradiance.x(), 2.0);
+
+    let (_, _, irradiance) = light.clone().set_falloff(Falloff::Linear).illuminate(Point::default()).unwrap();
+    assert_relative_eq!(irradiance.x(), 2.0);
+    let (_, _, irradiance) = light.set_falloff(Falloff::Constant).illuminate(Point::default()).unwrap();
+    assert_relative_eq!(irradiance.x(), 8.0);
+
+}
+
+#[test]
+fn test_spot_light(){
+
+    let white = Color::new(1.0, 1.0, 1.0);
+    let light = SpotLight::new(Point::new(0.0, 1.0, 0.0), Point::default(), white, 90.0, 0.5);
+
+    // full intensity inside the inner cone, a smooth edge, and nothing outside
+    let lit = |x: f32| light.illuminate(Point::new(x, 0.0, 0.0)).map_or(0.0, |(_, distance, irradiance)| irradiance.x()*distance*distance);
+
+    assert_relative_eq!(lit(0.0), 1.0);
+    assert_relative_eq!(lit(0.3), 1.0);
+    assert!(lit(0.6) > 0.0 && lit(0.6) < lit(0.5) && lit(0.5) < 1.0);
+    assert_eq!(lit(1.01), 0.0);
+
+}
+
+#[test]
+fn test_ies_profile(){
+
+    let white = Color::new(1.0, 1.0, 1.0);
+    let profile = IesProfile::parse(include_str!("../media/ies/wallwash.ies")).expect("Valid IES file.");
+
+    // nadir down, horizontal angle 0 towards +x and 90 towards -z
+    let light = PointLight::new(Point::default(), white).set_profile(profile.clone());
+    let lit = |light: &Light, p: Point| light.illuminate(p).map_or(0.0, |(_, distance, irradiance)| irradiance.x()*distance*distance);
+
+    assert_relative_eq!(lit(&light, Point::new(1.0, -1.0, 0.0)), 1.0, epsilon = 1e-4);
+    assert_relative_eq!(lit(&light, Point::new(0.0, -1.0, -1.0)), 0.6, epsilon = 1e-4);
+    assert_relative_eq!(lit(&light, Point::new(0.0, -1.0, 1.0)), 0.6, epsilon = 1e-4);
+    assert_relative_eq!(lit(&light, Point::new(-1.0, -1.0, 0.0)), 1.0/3.0, epsilon = 1e-4);
+    assert!(light.illuminate(Point::new(0.0, 1.0, 0.0)).is_none());
+
+    // spot lights carry the nadir along their axis, inside the cone
+    let light = SpotLight::new(Point::default(), Point::new(1.0, 0.0, 0.0), white, 120.0, 0.0).set_profile(profile);
+    assert_relative_eq!(lit(&light, Point::new(2.0, 0.0, 0.0)), 400.0/600.0, epsilon = 1e-4);
+    assert_eq!(lit(&light, Point::new(-2.0, 0.0, 0.0)), 0.0);
+
+}
+
+#[test]
+fn test_directional_light(){
+
+    let white = Color::new(1.0, 1.0, 1.0);
+
+    let light = DirectionalLight::new(Point::new(0.0, 2.0, 0.0), white, 0.0);
+    let (direction, distance, _) = light.illuminate(Point::default()).unwrap();
+    assert_eq!(direction, Point::new(0.0, 1.0, 0.0));
+    assert!(distance.is_infinite());
+
+    // a wide disk spreads the directions over its whole cone but not beyond
+    let light = DirectionalLight::new(Point::new(0.0, 1.0, 0.0), white, 20.0);
+    let cos_max = degrees_to_radians(10.0).cos();
+    let cosines: Vec<f32> = (0..1000).map(|_| light.illuminate(Point::default()).unwrap().0.y()).collect();
+
+    assert!(cosines.iter().all(|&c| c >= cos_max - 1e-5));
+    assert!(cosines.iter().any(|&c| c < 1.0 - 0.8*(1.0 - cos_max)));
+
+}
+
+#[test]
+fn test_area_lights(){
+
+    let white = Color::new(1.0, 1.0, 1.0);
+    let normal = Point::new(0.0, 1.0, 0.0);
+    let n = 20000;
+
+    // irradiance on a surface facing up, averaged over the sampled directions
+    let irradiance = |light: &Light, point: Point| {
+        (0..n).filter_map(|_| light.illuminate(point))
+            .map(|(direction, _, irradiance)| irradiance.x()*direction.dot(normal).max(0.0))
+            .sum::<f32>() / (n as f32)
+    };
+
+    // a sphere straight above lights like a point, pi * L * (r/d)^2
+    let sphere = AreaLight::sphere(Point::new(0.0, 4.0, 0.0), 1.0, white);
+    assert_relative_eq!(irradiance(&sphere, Point::default()), PI / 16.0, epsilon = 1e-3);
+
+    let (direction, distance, _) = sphere.illuminate(Point::default()).unwrap();
+    assert_relative_eq!((direction*distance - Point::new(0.0, 4.0, 0.0)).length(), 1.0, epsilon = 1e-4);
+    assert!(sphere.illuminate(Point::new(0.0, 4.5, 0.0)).is_none());
+
+    // a square and a disk facing down, against the closed forms for an emitter centered above
+    let square = AreaLight::quad(Point::new(-1.0, 1.0, -1.0), Point::new(2.0, 0.0, 0.0), Point::new(0.0, 0.0, 2.0), white);
+    let side = 1.0 / 2.0_f32.sqrt();
+    let closed_form = 4.0 * side * (side).atan();
+    assert_relative_eq!(irradiance(&square, Point::default()), closed_form, epsilon = 0.02);
+
+    let disk = AreaLight::disk(Point::new(0.0, 1.0, 0.0), Point::new(0.0, -1.0, 0.0), 1.0, white);
+    assert_relative_eq!(irradiance(&disk, Point::default()), PI / 2.0, epsilon = 0.05);
+
+    // the sampled points lie on the emitter
+    let (direction, distance, _) = square.illuminate(Point::default()).unwrap();
+    assert_relative_eq!((direction*distance).y(), 1.0, epsilon = 1e-4);
+
+    // they only emit on their outer side
+    assert!(square.illuminate(Point::new(0.0, 2.0, 0.0)).is_none());
+    assert!(disk.illuminate(Point::new(0.0, 2.0, 0.0)).is_none());
+
+    // off to the side, the spherical rectangle agrees with area sampling of a skewed copy of itself
+    let point = Point::new(2.0, -0.5, 1.0);
+    let skewed = AreaLight::quad(Point::new(-1.0, 1.0, -1.0), Point::new(2.0, 0.0, 0.0), Point::new(1e-3, 0.0, 2.0), white);
+    assert_relative_eq!(irradiance(&square, point), irradiance(&skewed, point), epsilon = 0.01);
+
+}
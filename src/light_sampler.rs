@@ -0,0 +1,451 @@
+use crate::light::Light;
+use crate::vec3::{Color, Point};
+
+use std::f32::consts::PI;
+
+// strategies to pick the lights connected to a surface by shadow rays
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum LightSampling {
+    // every light, one shadow ray each
+    All,
+    // one light picked uniformly
+    Uniform,
+    // one light picked with a probability proportional to its power
+    Power,
+    // one light picked by descending a light tree, by the importance of its nodes for the point
+    Tree,
+}
+
+//
+// LightBounds, what the light tree knows about a light or a group of lights
+#[derive(Debug, Clone, Copy)]
+pub struct LightBounds {
+
+    min: Point,
+    max: Point,
+    // emitted power
+    phi: f32,
+    // cone bounding the emission normals (axis, cosine of the half angle) and the cosine
+    // of the angle beyond those normals over which light is still emitted
+    w: Point,
+    cos_theta_o: f32,
+    cos_theta_e: f32,
+
+}
+
+impl LightBounds {
+
+    pub fn new(min: Point, max: Point, phi: f32, w: Point, cos_theta_o: f32, cos_theta_e: f32) -> Self {
+        Self { min, max, phi, w: Point::unit_vector(&w), cos_theta_o, cos_theta_e }
+    }
+
+    fn centroid(&self) -> Point {
+        (self.min + self.max) / 2.0
+    }
+
+    fn union(&self, other: &LightBounds) -> LightBounds {
+
+        let min = Point::new(self.min.x().min(other.min.x()), self.min.y().min(other.min.y()), self.min.z().min(other.min.z()));
+        let max = Point::new(self.max.x().max(other.max.x()), self.max.y().max(other.max.y()), self.max.z().max(other.max.z()));
+
+        let (w, cos_theta_o) = cone_union(self.w, self.cos_theta_o, other.w, other.cos_theta_o);
+
+        return Self { min, max, phi: self.phi + other.phi, w, cos_theta_o, cos_theta_e: self.cos_theta_e.min(other.cos_theta_e) };
+
+    }
+
+    // conservative estimate of the light received at point on a surface with the given normal
+    // (Conty Estevez and Kulla 2018, with the bounds of pbrt-v4)
+    fn importance(&self, point: Point, normal: Point) -> f32 {
+
+        let center = self.centroid();
+        let half_diagonal = (self.max - self.min).length() / 2.0;
+
+        // the distance is clamped so that points inside the bounds are not overweighted
+        let distance_square = (point - center).length_square().max(half_diagonal*half_diagonal).max(1e-8);
+
+        let wi = point - center;
+        let wi = if wi.length_square() > 0.0 { Point::unit_vector(&wi) } else { self.w };
+
+        let cos_theta_w = self.w.dot(wi);
+        let sin_theta_w = (1.0 - cos_theta_w*cos_theta_w).max(0.0).sqrt();
+
+        // angle subtended by the bounds, the whole sphere from inside them
+        let cos_theta_b = if distance_square > half_diagonal*half_diagonal {
+            (1.0 - half_diagonal*half_diagonal / distance_square).max(0.0).sqrt()
+        } else { -1.0 };
+        let sin_theta_b = (1.0 - cos_theta_b*cos_theta_b).max(0.0).sqrt();
+
+        // the smallest angle between the emission cone and the direction to the point
+        let sin_theta_o = (1.0 - self.cos_theta_o*self.cos_theta_o).max(0.0).sqrt();
+        let cos_theta_x = cos_sub_clamped(sin_theta_w, cos_theta_w, sin_theta_o, self.cos_theta_o);
+        let sin_theta_x = sin_sub_clamped(sin_theta_w, cos_theta_w, sin_theta_o, self.cos_theta_o);
+        let cos_theta_p = cos_sub_clamped(sin_theta_x, cos_theta_x, sin_theta_b, cos_theta_b);
+
+        if cos_theta_p <= self.cos_theta_e { return 0.0 }
+
+        let mut importance = self.phi*cos_theta_p / distance_square;
+
+        // the smallest incident angle on the surface
+        if normal.length_square() > 0.0 {
+            let cos_theta_i = wi.dot(normal).abs();
+            let sin_theta_i = (1.0 - cos_theta_i*cos_theta_i).max(0.0).sqrt();
+            importance *= cos_sub_clamped(sin_theta_i, cos_theta_i, sin_theta_b, cos_theta_b);
+        }
+
+        return importance.max(0.0);
+
+    }
+
+}
+
+// cos(max(0, a - b)) and sin(max(0, a - b)) from the sines and cosines of a and b
+fn cos_sub_clamped(sin_a: f32, cos_a: f32, sin_b: f32, cos_b: f32) -> f32 {
+    if cos_a > cos_b { return 1.0 }
+    return cos_a*cos_b + sin_a*sin_b;
+}
+
+fn sin_sub_clamped(sin_a: f32, cos_a: f32, sin_b: f32, cos_b: f32) -> f32 {
+    if cos_a > cos_b { return 0.0 }
+    return sin_a*cos_b - cos_a*sin_b;
+}
+
+// smallest cone containing two cones
+fn cone_union(w_a: Point, cos_a: f32, w_b: Point, cos_b: f32) -> (Point, f32) {
+
+    let (theta_a, theta_b) = (cos_a.clamp(-1.0, 1.0).acos(), cos_b.clamp(-1.0, 1.0).acos());
+    let theta_d = w_a.dot(w_b).clamp(-1.0, 1.0).acos();
+
+    if (theta_d + theta_b).min(PI) <= theta_a { return (w_a, cos_a) }
+    if (theta_d + theta_a).min(PI) <= theta_b { return (w_b, cos_b) }
+
+    let theta_o = (theta_a + theta_d + theta_b) / 2.0;
+
+    if theta_o >= PI { return (w_a, -1.0) }
+
+    // rotate w_a towards w_b about their common normal
+    let axis = w_a.cross(w_b);
+
+    if axis.length_square() < 1e-12 { return (w_a, -1.0) }
+
+    let axis = Point::unit_vector(&axis);
+    let theta_r = theta_o - theta_a;
+    let w = w_a*theta_r.cos() + axis.cross(w_a)*theta_r.sin() + axis*axis.dot(w_a)*(1.0 - theta_r.cos());
+
+    return (Point::unit_vector(&w), theta_o.cos());
+
+}
+
+//
+// AliasTable, constant time sampling of a discrete distribution (Vose's method)
+#[derive(Debug, Clone)]
+pub struct AliasTable {
+
+    // probability of keeping each bin instead of going to its alias
+    keep: Vec<f32>,
+    alias: Vec<usize>,
+    pmf: Vec<f32>,
+
+}
+
+impl AliasTable {
+
+    pub fn new(weights: &[f32]) -> Self {
+
+        let n = weights.len();
+        let total: f32 = weights.iter().map(|w| w.max(0.0)).sum();
+
+        // all zero weights fall back to a uniform distribution
+        let pmf: Vec<f32> = if total > 0.0 {
+            weights.iter().map(|w| w.max(0.0) / total).collect()
+        } else {
+            vec![1.0 / n as f32; n]
+        };
+
+        let mut scaled: Vec<f32> = pmf.iter().map(|p| p*n as f32).collect();
+        let mut keep = vec![1.0; n];
+        let mut alias: Vec<usize> = (0..n).collect();
+
+        let (mut small, mut large): (Vec<usize>, Vec<usize>) = (0..n).partition(|&i| scaled[i] < 1.0);
+
+        while let (Some(s), Some(&l)) = (small.pop(), large.last()) {
+
+            keep[s] = scaled[s];
+            alias[s] = l;
+
+            // the large bin gives what the small one lacks
+            scaled[l] -= 1.0 - scaled[s];
+
+            if scaled[l] < 1.0 {
+                large.pop();
+                small.push(l);
+            }
+
+        }
+
+        return Self { keep, alias, pmf };
+
+    }
+
+    pub fn len(&self) -> usize {
+        self.pmf.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.pmf.is_empty()
+    }
+
+    pub fn pmf(&self, index: usize) -> f32 {
+        self.pmf[index]
+    }
+
+    // index and its probability
+    pub fn sample(&self, u: f32) -> Option<(usize, f32)> {
+
+        if self.is_empty() { return None }
+
+        let x = u*self.len() as f32;
+        let bin = (x as usize).min(self.len() - 1);
+        let index = if x - (bin as f32) < self.keep[bin] { bin } else { self.alias[bin] };
+
+        return Some((index, self.pmf[index]));
+
+    }
+
+}
+
+//
+// LightTree, a bounding volume hierarchy over the bounded lights
+#[derive(Debug, Clone)]
+enum Node {
+    Leaf(LightBounds, usize),
+    Interior(LightBounds, Box<Node>, Box<Node>),
+}
+
+impl Node {
+
+    fn bounds(&self) -> &LightBounds {
+        match self {
+            Self::Leaf(bounds, _) => bounds,
+            Self::Interior(bounds, _, _) => bounds,
+        }
+    }
+
+}
+
+#[derive(Debug, Clone)]
+pub struct LightTree {
+
+    root: Option<Node>,
+
+}
+
+impl LightTree {
+
+    pub fn new(lights: &[(usize, LightBounds)]) -> Self {
+        Self { root: LightTree::build(lights.to_vec()) }
+    }
+
+    // median split along the axis with the largest spread of centroids
+    fn build(mut lights: Vec<(usize, LightBounds)>) -> Option<Node> {
+
+        if lights.len() <= 1 { return lights.pop().map(|(index, bounds)| Node::Leaf(bounds, index)) }
+
+        let centroids: Vec<Point> = lights.iter().map(|(_, bounds)| bounds.centroid()).collect();
+        let extent = |axis: fn(&Point) -> f32| {
+            let values = centroids.iter().map(axis);
+            values.clone().fold(f32::NEG_INFINITY, f32::max) - values.fold(f32::INFINITY, f32::min)
+        };
+        let extents = [extent(Point::x), extent(Point::y), extent(Point::z)];
+        let axis: fn(&Point) -> f32 = if extents[0] >= extents[1] && extents[0] >= extents[2] {
+            Point::x
+        } else if extents[1] >= extents[2] { Point::y } else { Point::z };
+
+        lights.sort_by(|(_, a), (_, b)| axis(&a.centroid()).total_cmp(&axis(&b.centroid())));
+        let right = lights.split_off(lights.len() / 2);
+
+        let left = LightTree::build(lights)?;
+        let right = LightTree::build(right)?;
+        let bounds = left.bounds().union(right.bounds());
+
+        return Some(Node::Interior(bounds, Box::new(left), Box::new(right)));
+
+    }
+
+    // index of a light and its probability, going down to the child of higher importance
+    // more often; None if no light can reach the point
+    pub fn sample(&self, point: Point, normal: Point, u: f32) -> Option<(usize, f32)> {
+
+        let mut node = self.root.as_ref()?;
+        let mut pmf = 1.0;
+        let mut u = u;
+
+        if node.bounds().importance(point, normal) <= 0.0 { return None }
+
+        loop {
+            match node {
+                Node::Leaf(_, index) => return Some((*index, pmf)),
+                Node::Interior(_, left, right) => {
+
+                    let importance = [left.bounds().importance(point, normal), right.bounds().importance(point, normal)];
+                    let total = importance[0] + importance[1];
+
+                    if total <= 0.0 { return None }
+
+                    // the random number is rescaled and reused down the tree
+                    let p_left = importance[0] / total;
+                    if u < p_left {
+                        u = (u / p_left).min(1.0 - f32::EPSILON);
+                        pmf *= p_left;
+                        node = left;
+                    } else {
+                        u = ((u - p_left) / (1.0 - p_left)).min(1.0 - f32::EPSILON);
+                        pmf *= 1.0 - p_left;
+                        node = right;
+                    }
+
+                }
+            }
+        }
+
+    }
+
+}
+
+//
+// LightSampler, the sampling structures over the lights of a world
+#[derive(Debug, Clone)]
+pub struct LightSampler {
+
+    count: usize,
+    // lights without bounds, such as directional ones, picked uniformly
+    infinite: Vec<usize>,
+    power: AliasTable,
+    power_indices: Vec<usize>,
+    tree: LightTree,
+
+}
+
+impl LightSampler {
+
+    pub fn new(lights: &[Light]) -> Self {
+
+        let bounded: Vec<(usize, LightBounds)> = lights.iter().enumerate()
+            .filter_map(|(i, light)| light.bounds().map(|bounds| (i, bounds)))
+            .collect();
+        let infinite = (0..lights.len()).filter(|i| bounded.iter().all(|(j, _)| j != i)).collect();
+
+        let power = AliasTable::new(&bounded.iter().map(|(_, bounds)| bounds.phi).collect::<Vec<f32>>());
+        let power_indices = bounded.iter().map(|(i, _)| *i).collect();
+
+        Self { count: lights.len(), infinite, power, power_indices, tree: LightTree::new(&bounded) }
+
+    }
+
+    // index of one light and the probability it was picked with, for a point on a surface
+    pub fn sample(&self, strategy: LightSampling, point: Point, normal: Point) -> Option<(usize, f32)> {
+
+        if self.count == 0 { return None }
+
+        let u = Point::random_float();
+
+        if strategy == LightSampling::Uniform || strategy == LightSampling::All {
+            let index = ((u*self.count as f32) as usize).min(self.count - 1);
+            return Some((index, 1.0 / self.count as f32));
+        }
+
+        // the infinite lights get one share each, the bounded ones one share together
+        let shares = self.infinite.len() + if self.power.is_empty() { 0 } else { 1 };
+        let p_infinite = self.infinite.len() as f32 / shares as f32;
+
+        if u < p_infinite {
+            let index = ((u / p_infinite * self.infinite.len() as f32) as usize).min(self.infinite.len() - 1);
+            return Some((self.infinite[index], 1.0 / shares as f32));
+        }
+
+        let u = ((u - p_infinite) / (1.0 - p_infinite)).min(1.0 - f32::EPSILON);
+
+        let (index, pmf) = match strategy {
+            LightSampling::Tree => self.tree.sample(point, normal, u)?,
+            _ => {
+                let (index, pmf) = self.power.sample(u)?;
+                (self.power_indices[index], pmf)
+            }
+        };
+
+        return Some((index, pmf*(1.0 - p_infinite)));
+
+    }
+
+}
+
+// scalar power of a colored emission, the average of the channels
+pub fn power(color: Color) -> f32 {
+    (color.x() + color.y() + color.z()) / 3.0
+}
+
+//
+// tests
+#[test]
+fn test_alias_table(){
+
+    let table = AliasTable::new(&[1.0, 3.0, 0.0, 4.0]);
+    let n = 80000;
+    let mut counts = [0; 4];
+
+    for _ in 0..n {
+        let (index, pmf) = table.sample(Point::random_float()).unwrap();
+        assert_eq!(pmf, table.pmf(index));
+        counts[index] += 1;
+    }
+
+    for (count, expected) in counts.iter().zip([0.125, 0.375, 0.0, 0.5]) {
+        assert_relative_eq!(*count as f32 / n as f32, expected, epsilon = 0.01);
+    }
+
+    assert!(AliasTable::new(&[]).sample(0.5).is_none());
+
+}
+
+#[test]
+fn test_light_tree(){
+
+    use crate::light::{AreaLight, DirectionalLight, PointLight};
+
+    let white = Color::new(1.0, 1.0, 1.0);
+    let normal = Point::new(0.0, 1.0, 0.0);
+
+    // a row of point lights above the ground, a quad facing away and the sun
+    let mut lights: Vec<Light> = (0..16).map(|i| PointLight::new(Point::new(i as f32, 1.0, 0.0), white)).collect();
+    lights.push(AreaLight::quad(Point::new(0.0, 2.0, 0.0), Point::new(0.0, 0.0, 1.0), Point::new(1.0, 0.0, 0.0), white));
+    lights.push(DirectionalLight::new(normal, white, 0.0));
+
+    let sampler = LightSampler::new(&lights);
+    let point = Point::new(3.0, 0.0, 0.0);
+
+    for strategy in [LightSampling::Uniform, LightSampling::Power, LightSampling::Tree] {
+
+        // unbiased: every light that can be picked is, and their probabilities add up to one
+        let n = 40000;
+        let mut counts = vec![0; lights.len()];
+        let mut pmfs = vec![0.0; lights.len()];
+
+        for _ in 0..n {
+            let (index, pmf) = sampler.sample(strategy, point, normal).expect("Some lights reach the point.");
+            counts[index] += 1;
+            pmfs[index] = pmf;
+        }
+
+        let reachable = if strategy == LightSampling::Tree { 17 } else { 18 };
+        assert_eq!(counts.iter().filter(|&&count| count > 0).count(), reachable);
+        assert_relative_eq!(pmfs.iter().sum::<f32>(), 1.0, epsilon = 1e-4);
+
+        // the tree never picks the quad facing away, and favours the light right above the point
+        if strategy == LightSampling::Tree {
+            assert_eq!(counts[16], 0);
+            assert!(counts[3] > 4*counts[12]);
+        }
+
+    }
+
+}
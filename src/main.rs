@@ -3,6 +3,15 @@ use std::fs::File;
 
 use raytracer::scenes::final_scene;
 // use raytracer::scenes::penultimate_scene;
+// use raytracer::scenes::procedural_scene;
+// use raytracer::scenes::dispersion_scene;
+// use raytracer::scenes::volume_scene;
+// use raytracer::scenes::lights_scene;
+// use raytracer::scenes::area_lights_scene;
+// use raytracer::scenes::many_lights_scene;
+// use raytracer::scenes::ies_scene;
+// use raytracer::scenes::sky_scene;
+// use raytracer::scenes::cornell_box_scene;
 
 fn main() {
     
@@ -14,6 +23,6 @@ fn main() {
     
     // write image
     let mut file =  File::create("image.ppm").expect("Unable to create image file.");
-    file.write(&buffer).expect("Unable to write image file.");
+    file.write_all(&buffer).expect("Unable to write image file.");
 
 }
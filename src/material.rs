@@ -1,94 +1,534 @@
 use crate::{hittable::HitRecord, ray::Ray, vec3::{Color, Point}};
+use crate::medium::{Homogeneous, Medium, Phase};
+use crate::microfacet::{fresnel_conductor, fresnel_dielectric, fresnel_schlick, fresnel_thin_film, Ggx};
+use crate::normal_map::NormalMap;
+use crate::onb::Onb;
+use crate::spectrum::{Dispersion, Wavelengths, RGB_WAVELENGTHS};
+use crate::texture::{Lookup, Texture};
 
 use rand_distr::num_traits::pow;
 
+use std::f32::consts::PI;
+use std::sync::Arc;
+
+// strata per dimension of the visible normals averaged by the principled diffuse weight
+const DIFFUSE_STRATA: usize = 4;
+
 //
 // main trait
 pub trait Scatter {
     
-    fn scatter(&self, ray_in: &Ray, record: &HitRecord) -> Option<(Ray, Color)>;
+    // scattered ray, its weight f*cos/pdf, and whether it was sampled from a perfectly specular lobe
+    fn scatter(&self, ray_in: &Ray, record: &HitRecord) -> Option<(Ray, Color, bool)>;
+
+    // bsdf times the cosine towards direction, for the light sources: perfectly specular lobes
+    // are left out, only the scattered rays can find what they reflect
+    fn eval(&self, ray_in: &Ray, record: &HitRecord, direction: Point) -> Color;
 
 }
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone)]
 pub enum Material {
     Lambertian(Lambertian),
+    OrenNayar(OrenNayar),
     Metal(Metal),
     Dielectric(Dielectric),
+    RoughDielectric(RoughDielectric),
+    Principled(Principled),
+    Mapped(Mapped),
+    Layered(Layered),
+    Subsurface(Subsurface),
+    Volume(Volume),
+    Mix(Mix),
+    Cutout(Cutout),
+    DiffuseLight(DiffuseLight),
 }
 
 impl Scatter for Material {
     
-    fn scatter(&self, ray_in: &Ray, record: &HitRecord) -> Option<(Ray, Color)> {
+    fn scatter(&self, ray_in: &Ray, record: &HitRecord) -> Option<(Ray, Color, bool)> {
         match self {
-            Self::Lambertian(l) => l.scatter(record),
+            Self::Lambertian(l) => l.scatter(ray_in, record),
+            Self::OrenNayar(o) => o.scatter(ray_in, record),
             Self::Metal(m) => m.scatter(ray_in, record),
             Self::Dielectric(d) => d.scatter(ray_in, record),
+            Self::RoughDielectric(d) => d.scatter(ray_in, record),
+            Self::Principled(p) => p.scatter(ray_in, record),
+            Self::Mapped(m) => m.scatter(ray_in, record),
+            Self::Layered(l) => l.scatter(ray_in, record),
+            Self::Subsurface(s) => s.scatter(ray_in, record),
+            Self::Volume(v) => v.scatter(ray_in, record),
+            Self::Mix(m) => m.scatter(ray_in, record),
+            Self::Cutout(c) => c.scatter(ray_in, record),
+            Self::DiffuseLight(_) => None,
             // Handle other materials here
         }
     }
 
+    fn eval(&self, ray_in: &Ray, record: &HitRecord, direction: Point) -> Color {
+        match self {
+            Self::Lambertian(l) => l.eval(ray_in, record, direction),
+            Self::OrenNayar(o) => o.eval(ray_in, record, direction),
+            Self::Metal(m) => m.eval(ray_in, record, direction),
+            Self::RoughDielectric(d) => d.eval(ray_in, record, direction),
+            Self::Principled(p) => p.eval(ray_in, record, direction),
+            Self::Mapped(m) => m.eval(ray_in, record, direction),
+            Self::Layered(l) => l.eval(ray_in, record, direction),
+            Self::Mix(m) => m.eval(ray_in, record, direction),
+            Self::Cutout(c) => c.base.eval(ray_in, record, direction),
+            // smooth interfaces, index matched boundaries and emitters
+            Self::Dielectric(_) | Self::Subsurface(_) | Self::Volume(_) | Self::DiffuseLight(_) => Color::default(),
+        }
+    }
+
+}
+
+impl Material {
+
+    // medium filling closed objects made of this material, sampled by the camera
+    // on the path segments that leave the object through its back faces
+    pub fn interior(&self) -> Option<&Medium> {
+        match self {
+            Self::Subsurface(s) => Some(&s.medium),
+            Self::Volume(v) => Some(&v.medium),
+            Self::Mapped(m) => m.base.interior(),
+            _ => None,
+        }
+    }
+
+    // fraction of the light a shadow ray carries through the surface, none for opaque ones: volume
+    // boundaries are index matched, and subsurface objects let the light out of their inside through
+    // the Fresnel transmittance of their surface, the refraction being neglected
+    pub fn shadow_transmittance(&self, ray: &Ray, record: &HitRecord) -> Option<f32> {
+        match self {
+            Self::Volume(_) => Some(1.0),
+            Self::Subsurface(s) if !record.front_face => {
+                let cos_i = record.normal.dot(-Point::unit_vector(&ray.direction()));
+                Some(1.0 - fresnel_dielectric(cos_i, 1.0 / s.surface.refraction_index))
+            }
+            Self::Mapped(m) => m.base.shadow_transmittance(ray, record),
+            _ => None,
+        }
+    }
+
+    // probability that a ray stops at the hit instead of going through it as if it missed
+    pub fn opacity(&self, record: &HitRecord) -> f32 {
+        match self {
+            Self::Cutout(c) => c.opacity.value(record.u, record.v, record.hit_location).x().clamp(0.0, 1.0) * c.base.opacity(record),
+            Self::Mapped(m) => m.base.opacity(record),
+            Self::Mix(m) => {
+                let factor = m.factor.value(record.u, record.v, record.hit_location).x().clamp(0.0, 1.0);
+                m.first.opacity(record)*(1.0 - factor) + m.second.opacity(record)*factor
+            }
+            _ => 1.0,
+        }
+    }
+
+    // radiance the surface emits towards the ray
+    pub fn emitted(&self, ray_in: &Ray, record: &HitRecord) -> Color {
+        match self {
+            Self::DiffuseLight(l) if record.front_face => ray_in.spectrum(l.radiance),
+            Self::Mapped(m) => m.base.emitted(ray_in, record),
+            Self::Cutout(c) => c.base.emitted(ray_in, record),
+            Self::Mix(m) => {
+                let factor = m.factor.value(record.u, record.v, record.hit_location).x().clamp(0.0, 1.0);
+                m.first.emitted(ray_in, record)*(1.0 - factor) + m.second.emitted(ray_in, record)*factor
+            }
+            _ => Color::default(),
+        }
+    }
+
+    // radiance of the emitters that can be sampled as area lights, averaged over the opacity and
+    // the weights of the wrappers: an area light is uniform, so varying masks are approximated by
+    // their mean while the hits still see them exactly
+    pub fn emission(&self) -> Option<Color> {
+
+        let average = |texture: &Texture| texture.average().x().clamp(0.0, 1.0);
+
+        match self {
+            Self::DiffuseLight(l) => Some(l.radiance),
+            Self::Mapped(m) => m.base.emission(),
+            Self::Cutout(c) => c.base.emission().map(|radiance| radiance*average(&c.opacity)),
+            Self::Mix(m) => match (m.first.emission(), m.second.emission()) {
+                (None, None) => None,
+                (first, second) => {
+                    let factor = average(&m.factor);
+                    Some(first.unwrap_or_default()*(1.0 - factor) + second.unwrap_or_default()*factor)
+                }
+            },
+            _ => None,
+        }
+
+    }
+
+}
+
+// mirrors a direction across the geometric tangent plane if it ended up on the wrong side,
+// so that shading normals never send light through the surface
+fn keep_on_side(direction: Point, normal: Point, above: bool) -> Point {
+
+    let cosine = direction.dot(normal);
+
+    if (cosine > 0.0) == above { return direction }
+
+    return direction - normal*cosine*2.0;
+
+}
+
+// f*cos of a single scattering microfacet reflection without the Fresnel term, with the
+// microfacet normal; the weight of the visible normal sampling is this over its pdf
+fn microfacet_reflection(ggx: &Ggx, wo: Point, wi: Point) -> Option<(Point, f32)> {
+
+    if ggx.is_smooth() || wo.z() <= 0.0 || wi.z() <= 0.0 { return None }
+
+    let h = Point::unit_vector(&(wo + wi));
+
+    return Some((h, ggx.d(h)*ggx.g2(wo, wi) / (4.0*wo.z())));
+
+}
+
+// same for the refraction through the microfacets, eta = n_transmitted / n_incident, without
+// the radiance scaling by eta^2 that the sampled paths leave out as well (Walter et al. 2007)
+fn microfacet_transmission(ggx: &Ggx, wo: Point, wi: Point, eta: f32) -> Option<(Point, f32)> {
+
+    if ggx.is_smooth() || wo.z() <= 0.0 || wi.z() >= 0.0 { return None }
+
+    let h = Point::unit_vector(&-(wo + wi*eta));
+    let h = if h.z() < 0.0 { -h } else { h };
+
+    let (cos_o, cos_i) = (wo.dot(h), wi.dot(h));
+
+    if cos_o <= 0.0 || cos_i >= 0.0 { return None }
+
+    let denominator = cos_o + eta*cos_i;
+    let value = ggx.d(h)*ggx.g2(wo, wi)*cos_o*(-cos_i)*eta*eta / (wo.z()*denominator*denominator);
+
+    return Some((h, value));
+
 }
 
 //
 // Lambertian (diffuse)
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone)]
 pub struct Lambertian {
 
-    albedo: Color,
+    albedo: Texture,
 
 }
 
 impl Lambertian {
 
     pub fn new(albedo: Color) -> Material {
+        Material::Lambertian(Self { albedo: Texture::Solid(albedo) })
+    }
+
+    pub fn textured(albedo: Texture) -> Material {
         Material::Lambertian(Self { albedo })
     }
 
-    fn scatter(&self, record: &HitRecord) -> Option<(Ray, Color)> {
+    fn scatter(&self, ray_in: &Ray, record: &HitRecord) -> Option<(Ray, Color, bool)> {
 
-        let mut scatter_direction = record.normal + Point::random_on_sphere();
+        let mut scatter_direction = record.shading_normal + Point::random_on_sphere();
 
-        if scatter_direction == Point::new(0.0, 0.0, 0.0) { scatter_direction = record.normal }
+        if scatter_direction == Point::new(0.0, 0.0, 0.0) { scatter_direction = record.shading_normal }
+
+        let scatter_direction = keep_on_side(scatter_direction, record.normal, true);
 
         let ray_out = Ray::new(record.hit_location, scatter_direction);
 
-        let attenuation = self.albedo;
+        let attenuation = self.albedo.value(record.u, record.v, record.hit_location);
+
+        return Some((ray_out, ray_in.spectrum(attenuation), false));
+
+    }
+
+    fn eval(&self, ray_in: &Ray, record: &HitRecord, direction: Point) -> Color {
+
+        let direction = Point::unit_vector(&direction);
 
-        return Some((ray_out, attenuation));
+        if direction.dot(record.normal) <= 0.0 { return Color::default() }
+
+        let cosine = record.shading_normal.dot(direction).max(0.0);
+        let albedo = self.albedo.value(record.u, record.v, record.hit_location);
+
+        return ray_in.spectrum(albedo)*(cosine / PI);
+
+    }
+
+}
+
+//
+// OrenNayar (rough diffuse)
+#[derive(Debug, Clone)]
+pub struct OrenNayar {
+
+    albedo: Texture,
+    // in [0:1], 0 is Lambertian
+    roughness: f32,
+
+}
+
+impl OrenNayar {
+
+    pub fn new(albedo: Color, roughness: f32) -> Material {
+        OrenNayar::textured(Texture::Solid(albedo), roughness)
+    }
+
+    pub fn textured(albedo: Texture, roughness: f32) -> Material {
+        Material::OrenNayar(Self { albedo, roughness: roughness.clamp(0.0, 1.0) })
+    }
+
+    fn scatter(&self, ray_in: &Ray, record: &HitRecord) -> Option<(Ray, Color, bool)> {
+
+        let normal = record.shading_normal;
+        let wo = -Point::unit_vector(&ray_in.direction());
+
+        let mut scatter_direction = normal + Point::random_on_sphere();
+
+        if scatter_direction.length_square() < 1e-12 { scatter_direction = normal }
+
+        let wi = Point::unit_vector(&keep_on_side(scatter_direction, record.normal, true));
+
+        // with cosine sampling the weight is albedo * (A + B*s/t)
+        let ray_out = Ray::new(record.hit_location, wi);
+        let attenuation = self.albedo.value(record.u, record.v, record.hit_location);
+
+        return Some((ray_out, ray_in.spectrum(attenuation)*self.factor(normal, wo, wi), false));
+
+    }
+
+    fn eval(&self, ray_in: &Ray, record: &HitRecord, direction: Point) -> Color {
+
+        let wi = Point::unit_vector(&direction);
+
+        if wi.dot(record.normal) <= 0.0 { return Color::default() }
+
+        let normal = record.shading_normal;
+        let wo = -Point::unit_vector(&ray_in.direction());
+
+        let cosine = normal.dot(wi).max(0.0);
+        let albedo = self.albedo.value(record.u, record.v, record.hit_location);
+
+        return ray_in.spectrum(albedo)*(self.factor(normal, wo, wi)*cosine / PI);
+
+    }
+
+    // Fujii's improved Oren-Nayar, the bsdf is albedo/pi times this factor A + B*s/t
+    fn factor(&self, normal: Point, wo: Point, wi: Point) -> f32 {
+
+        let a = 1.0 / (1.0 + (0.5 - 2.0/(3.0*PI))*self.roughness);
+        let b = self.roughness*a;
+
+        let (cos_i, cos_o) = (normal.dot(wi).max(0.0), normal.dot(wo).max(0.0));
+        let s = wi.dot(wo) - cos_i*cos_o;
+        let t = if s > 0.0 { cos_i.max(cos_o).max(1e-6) } else { 1.0 };
+
+        return a + b*s/t;
 
     }
 
 }
 
 //
-// Metal
+// Metal (GGX microfacet conductor)
 #[derive(Debug, Clone, Copy)]
+pub enum Fresnel {
+    // artist friendly reflectance at normal incidence
+    Schlick(Color),
+    // complex index of refraction eta + i*k per channel
+    Conductor(Color, Color),
+}
+
+impl Fresnel {
+
+    fn evaluate(&self, cos_i: f32) -> Color {
+        match self {
+            Self::Schlick(f0) => fresnel_schlick(cos_i, *f0),
+            Self::Conductor(eta, k) => fresnel_conductor(cos_i, *eta, *k),
+        }
+    }
+
+    // complex index of refraction, a Schlick reflectance is taken as a real index with the same f0
+    fn complex_index(&self) -> (Color, Color) {
+        match self {
+            Self::Schlick(f0) => {
+                let eta = |f0: f32| {
+                    let r = f0.clamp(0.0, 0.99).sqrt();
+                    (1.0 + r) / (1.0 - r)
+                };
+                (Color::new(eta(f0.x()), eta(f0.y()), eta(f0.z())), Color::default())
+            }
+            Self::Conductor(eta, k) => (*eta, *k),
+        }
+    }
+
+}
+
+//
+// ThinFilm (interference coating on dielectrics and conductors)
+#[derive(Debug, Clone)]
+pub struct ThinFilm {
+
+    // thickness in nm, read from the red channel
+    thickness: Texture,
+    refraction_index: f32,
+
+}
+
+impl ThinFilm {
+
+    pub fn new(thickness: f32, refraction_index: f32) -> Self {
+        ThinFilm::textured(Texture::Solid(Color::new(thickness, thickness, thickness)), refraction_index)
+    }
+
+    pub fn textured(thickness: Texture, refraction_index: f32) -> Self {
+        Self { thickness, refraction_index }
+    }
+
+    // reflectance over a substrate of complex index eta + i*k, evaluated at the carried wavelengths
+    // for spectral rays and at one representative wavelength per channel for rgb rays, which
+    // washes out the colors of films thicker than about a micron
+    fn reflectance(&self, ray_in: &Ray, record: &HitRecord, cos_i: f32, outside: f32, eta: Color, k: Color) -> Color {
+
+        let thickness = self.thickness.value(record.u, record.v, record.hit_location).x().max(0.0);
+
+        let (lambdas, eta, k) = match ray_in.wavelengths() {
+            Some(w) => (w.lambdas(), w.upsample(eta), w.upsample(k)),
+            None => (Color::new(RGB_WAVELENGTHS[0], RGB_WAVELENGTHS[1], RGB_WAVELENGTHS[2]), eta, k),
+        };
+
+        let channel = |lambda: f32, eta: f32, k: f32| {
+            fresnel_thin_film(cos_i, outside, self.refraction_index, thickness, eta, k, lambda)
+        };
+
+        return Color::new(channel(lambdas.x(), eta.x(), k.x()), channel(lambdas.y(), eta.y(), k.y()), channel(lambdas.z(), eta.z(), k.z()));
+
+    }
+
+}
+
+#[derive(Debug, Clone)]
 pub struct Metal {
 
-    albedo: Color,
-    fuzz: f32,
+    fresnel: Fresnel,
+    roughness: Texture,
+    anisotropy: f32,
+    film: Option<ThinFilm>,
 
 }
 
 impl Metal {
 
-    pub fn new(albedo: Color, fuzz: f32) -> Material {
-        Material::Metal(Self { albedo, fuzz })
+    pub fn new(albedo: Color, roughness: f32) -> Material {
+        Metal::textured(Fresnel::Schlick(albedo), Texture::Solid(Color::new(roughness, roughness, roughness)), 0.0)
+    }
+
+    pub fn conductor(eta: Color, k: Color, roughness: f32, anisotropy: f32) -> Material {
+        Metal::textured(Fresnel::Conductor(eta, k), Texture::Solid(Color::new(roughness, roughness, roughness)), anisotropy)
+    }
+
+    pub fn textured(fresnel: Fresnel, roughness: Texture, anisotropy: f32) -> Material {
+        Material::Metal(Self { fresnel, roughness, anisotropy, film: None })
+    }
+
+    // conductor under an interference coating, e.g. anodised or heat tinted metal
+    pub fn filmed(fresnel: Fresnel, roughness: Texture, anisotropy: f32, film: ThinFilm) -> Material {
+        Material::Metal(Self { fresnel, roughness, anisotropy, film: Some(film) })
+    }
+
+    // measured complex IOR at roughly 650, 550 and 450 nm
+    pub fn gold(roughness: f32) -> Material {
+        Metal::conductor(Color::new(0.18299, 0.42108, 1.37340), Color::new(3.42420, 2.34590, 1.77040), roughness, 0.0)
+    }
+
+    pub fn silver(roughness: f32) -> Material {
+        Metal::conductor(Color::new(0.15943, 0.14512, 0.13547), Color::new(3.92910, 3.19000, 2.38080), roughness, 0.0)
+    }
+
+    pub fn copper(roughness: f32) -> Material {
+        Metal::conductor(Color::new(0.27105, 0.67693, 1.31640), Color::new(3.60920, 2.62480, 2.29210), roughness, 0.0)
+    }
+
+    pub fn aluminium(roughness: f32) -> Material {
+        Metal::conductor(Color::new(1.65740, 0.88036, 0.52123), Color::new(9.22380, 6.26950, 4.83700), roughness, 0.0)
+    }
+
+    pub fn chromium(roughness: f32) -> Material {
+        Metal::conductor(Color::new(3.10710, 3.18120, 2.32300), Color::new(3.33140, 3.33160, 3.13350), roughness, 0.0)
+    }
+
+    pub fn iron(roughness: f32) -> Material {
+        Metal::conductor(Color::new(2.91140, 2.94970, 2.58450), Color::new(3.08930, 2.93180, 2.76700), roughness, 0.0)
+    }
+
+    fn distribution(&self, record: &HitRecord) -> Ggx {
+        let roughness = self.roughness.value(record.u, record.v, record.hit_location).x();
+        Ggx::from_roughness(roughness, self.anisotropy)
+    }
+
+    // reflectance in the space the ray is rendered in
+    fn reflectance(&self, ray_in: &Ray, record: &HitRecord, cos_i: f32) -> Color {
+        match &self.film {
+            None => ray_in.spectrum(self.fresnel.evaluate(cos_i)),
+            Some(film) => {
+                let (eta, k) = self.fresnel.complex_index();
+                film.reflectance(ray_in, record, cos_i, 1.0, eta, k)
+            }
+        }
+    }
+
+    fn scatter(&self, ray_in: &Ray, record: &HitRecord) -> Option<(Ray, Color, bool)> {
+
+        // local frame aligned with dpdu, so that anisotropy follows the surface parameterization
+        let frame = Onb::from_tangent(record.shading_normal, record.dpdu);
+        let wo = frame.to_local(-Point::unit_vector(&ray_in.direction()));
+
+        if wo.z() <= 0.0 { return None }
+
+        let ggx = self.distribution(record);
+
+        let (wi, attenuation) = if ggx.is_smooth() {
+
+            let wi = Point::new(-wo.x(), -wo.y(), wo.z());
+            (wi, self.reflectance(ray_in, record, wo.z()))
+
+        } else {
+
+            // sample visible normals, the weight f*cos/pdf reduces to F*G2/G1
+            let h = ggx.sample_visible(wo, Point::random_float(), Point::random_float());
+            let wi = Point::reflect(-wo, h);
+
+            if wi.z() <= 0.0 { return None }
+
+            // compensate the energy lost to single scattering, tinted by the reflectance at normal incidence
+            let compensation = ggx.multiple_scattering(wo, self.reflectance(ray_in, record, 1.0));
+
+            (wi, self.reflectance(ray_in, record, wo.dot(h)) * compensation * (ggx.g2(wo, wi) / ggx.g1(wo)))
+
+        };
+
+        let direction = keep_on_side(frame.to_world(wi), record.normal, true);
+        let ray_out = Ray::new(record.hit_location, direction);
+
+        return Some((ray_out, attenuation, ggx.is_smooth()));
+
     }
 
-    fn scatter(&self, ray_in: &Ray, record: &HitRecord) -> Option<(Ray, Color)> {
+    fn eval(&self, ray_in: &Ray, record: &HitRecord, direction: Point) -> Color {
+
+        if direction.dot(record.normal) <= 0.0 { return Color::default() }
 
-        let reflected = Point::reflect(ray_in.direction(), record.normal);
-        let reflected = Point::unit_vector(&reflected) + Point::random_on_sphere()*self.fuzz;
+        let frame = Onb::from_tangent(record.shading_normal, record.dpdu);
+        let wo = frame.to_local(-Point::unit_vector(&ray_in.direction()));
+        let wi = frame.to_local(Point::unit_vector(&direction));
 
-        if reflected.dot(record.normal) < 0.0 { return None }
+        let ggx = self.distribution(record);
 
-        let ray_out = Ray::new(record.hit_location, reflected);
+        let Some((h, value)) = microfacet_reflection(&ggx, wo, wi) else { return Color::default() };
 
-        let attenuation = self.albedo;
+        let compensation = ggx.multiple_scattering(wo, self.reflectance(ray_in, record, 1.0));
 
-        return Some((ray_out, attenuation));
+        return self.reflectance(ray_in, record, wo.dot(h)) * compensation * value;
 
     }
 
@@ -96,40 +536,111 @@ impl Metal {
 
 //
 // Dielectric
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone)]
 pub struct Dielectric {
 
     refraction_index: f32,
+    absorption: Color,
+    dispersion: Option<Dispersion>,
+    film: Option<ThinFilm>,
 
 }
 
 impl Dielectric {
 
     pub fn new(refraction_index: f32) -> Material {
-        Material::Dielectric(Self { refraction_index })
+        Dielectric::absorbing(refraction_index, Color::default())
+    }
+
+    // absorption coefficient per unit length (Beer-Lambert)
+    pub fn absorbing(refraction_index: f32, absorption: Color) -> Material {
+        Material::Dielectric(Self { refraction_index, absorption, dispersion: None, film: None })
+    }
+
+    // glass that transmits the given color after travelling the given distance inside,
+    // a distance that is not positive is taken as a very short one
+    pub fn tinted(refraction_index: f32, color: Color, distance: f32) -> Material {
+
+        let distance = distance.max(1e-6);
+        let coefficient = |c: f32| -c.clamp(1e-6, 1.0).ln() / distance;
+        let absorption = Color::new(coefficient(color.x()), coefficient(color.y()), coefficient(color.z()));
+
+        Dielectric::absorbing(refraction_index, absorption)
+
     }
 
-    fn scatter(&self, ray_in: &Ray, record: &HitRecord) -> Option<(Ray, Color)> {
+    // wavelength dependent index of refraction, rgb rendering uses the index at the sodium d-line
+    pub fn dispersive(dispersion: Dispersion) -> Material {
+        let refraction_index = dispersion.refraction_index(587.6);
+        Material::Dielectric(Self { refraction_index, absorption: Color::default(), dispersion: Some(dispersion), film: None })
+    }
+
+    // interface under a thin film on its outer side: a soap bubble is a film around air
+    // (refraction index 1), an oil slick a film on water
+    pub fn filmed(refraction_index: f32, film: ThinFilm) -> Material {
+        Material::Dielectric(Self { refraction_index, absorption: Color::default(), dispersion: None, film: Some(film) })
+    }
+
+    fn scatter(&self, ray_in: &Ray, record: &HitRecord) -> Option<(Ray, Color, bool)> {
 
-        let eta_frac = if record.front_face { 1.0/self.refraction_index } else { self.refraction_index };
+        // spectral rays see the index of their hero wavelength, and the companion wavelengths
+        // are dropped since they would refract in other directions
+        let (refraction_index, wavelengths) = match (self.dispersion, ray_in.wavelengths()) {
+            (Some(dispersion), Some(wavelengths)) => (dispersion.refraction_index(wavelengths.hero()), Some(wavelengths.terminate_secondary())),
+            _ => (self.refraction_index, ray_in.wavelengths()),
+        };
+
+        let eta_frac = if record.front_face { 1.0/refraction_index } else { refraction_index };
         let ray_in_normalized = Point::unit_vector(&ray_in.direction());
 
-        let cos_theta = (record.normal.dot(-ray_in_normalized)).min(1.0);
+        let normal = record.shading_normal;
+
+        let cos_theta = (normal.dot(-ray_in_normalized)).clamp(0.0, 1.0);
         let sin_theta = (1.0 - cos_theta*cos_theta).sqrt();
 
-        let direction: Point;
         let cannot_refract = eta_frac * sin_theta > 1.0;
 
-        if cannot_refract || Dielectric::reflectance(cos_theta, eta_frac) > Point::random_float() {
-            direction = Point::reflect(ray_in_normalized, record.normal);
+        // a film reflects each channel differently, the branch is then picked by the average
+        // reflectance and the channels are reweighted
+        let (reflect, weight) = match &self.film {
+            _ if cannot_refract => (true, Color::new(1.0, 1.0, 1.0)),
+            None => (Dielectric::reflectance(cos_theta, eta_frac) > Point::random_float(), Color::new(1.0, 1.0, 1.0)),
+            Some(film) => {
+                let (outside, inside) = if record.front_face { (1.0, refraction_index) } else { (refraction_index, 1.0) };
+                let reflectance = film.reflectance(ray_in, record, cos_theta, outside, Color::new(inside, inside, inside), Color::default());
+                let probability = ((reflectance.x() + reflectance.y() + reflectance.z()) / 3.0).clamp(1e-4, 1.0 - 1e-4);
+                if probability > Point::random_float() {
+                    (true, reflectance / probability)
+                } else {
+                    (false, (-reflectance + 1.0) / (1.0 - probability))
+                }
+            }
+        };
+
+        let direction = if reflect {
+            keep_on_side(Point::reflect(ray_in_normalized, normal), record.normal, true)
         } else {
-            direction = Point::refract(ray_in_normalized, record.normal, eta_frac);
-        }
+            keep_on_side(Point::refract(ray_in_normalized, normal, eta_frac), record.normal, false)
+        };
 
-        let ray_out = Ray::new(record.hit_location, direction);
-        let attenuation = Color::new(1.0, 1.0, 1.0);
+        let ray_out = Ray::new(record.hit_location, direction).set_wavelengths(wavelengths);
+
+        // hitting the surface from the inside means the ray travelled through the medium
+        let attenuation = if record.front_face {
+            Color::new(1.0, 1.0, 1.0)
+        } else {
+            let distance = record.t * ray_in.direction().length();
+            (-self.absorption*distance).exp()
+        };
+        let mut attenuation = ray_in.spectrum(attenuation) * weight;
+
+        // the hero wavelength now carries the whole path, as the only one of three samples
+        let terminated = |w: Option<Wavelengths>| w.is_some_and(|w| w.is_terminated());
+        if terminated(wavelengths) && !terminated(ray_in.wavelengths()) {
+            attenuation = attenuation * Color::new(3.0, 0.0, 0.0);
+        }
 
-        return Some((ray_out, attenuation));
+        return Some((ray_out, attenuation, true));
 
     }
 
@@ -145,3 +656,1237 @@ impl Dielectric {
     }
 
 }
+
+//
+// RoughDielectric (frosted glass)
+#[derive(Debug, Clone)]
+pub struct RoughDielectric {
+
+    refraction_index: f32,
+    roughness: Texture,
+
+}
+
+impl RoughDielectric {
+
+    pub fn new(refraction_index: f32, roughness: f32) -> Material {
+        RoughDielectric::textured(refraction_index, Texture::Solid(Color::new(roughness, roughness, roughness)))
+    }
+
+    pub fn textured(refraction_index: f32, roughness: Texture) -> Material {
+        Material::RoughDielectric(Self { refraction_index, roughness })
+    }
+
+    fn scatter(&self, ray_in: &Ray, record: &HitRecord) -> Option<(Ray, Color, bool)> {
+
+        // eta is the ratio of the transmitted over the incident side
+        let eta = if record.front_face { self.refraction_index } else { 1.0/self.refraction_index };
+
+        let frame = Onb::from_tangent(record.shading_normal, record.dpdu);
+        let wo = frame.to_local(-Point::unit_vector(&ray_in.direction()));
+
+        if wo.z() <= 0.0 { return None }
+
+        let roughness = self.roughness.value(record.u, record.v, record.hit_location).x();
+        let ggx = Ggx::from_roughness(roughness, 0.0);
+
+        // microfacet normal, the macro normal for a smooth surface
+        let h = if ggx.is_smooth() {
+            Point::new(0.0, 0.0, 1.0)
+        } else {
+            ggx.sample_visible(wo, Point::random_float(), Point::random_float())
+        };
+
+        // Walter et al. 2007, choosing between reflection and refraction by the exact Fresnel term
+        // makes the weight f*cos/pdf reduce to G2/G1 for both
+        let reflectance = fresnel_dielectric(wo.dot(h), eta);
+        let reflect = reflectance > Point::random_float();
+
+        let wi = if reflect { Point::reflect(-wo, h) } else { Point::refract(-wo, h, 1.0/eta) };
+
+        if reflect == (wi.z() <= 0.0) { return None }
+
+        let attenuation = if ggx.is_smooth() { 1.0 } else { ggx.g2(wo, wi) / ggx.g1(wo) };
+
+        let direction = keep_on_side(frame.to_world(wi), record.normal, reflect);
+        let ray_out = Ray::new(record.hit_location, direction);
+
+        return Some((ray_out, ray_in.spectrum(Color::new(attenuation, attenuation, attenuation)), ggx.is_smooth()));
+
+    }
+
+    fn eval(&self, ray_in: &Ray, record: &HitRecord, direction: Point) -> Color {
+
+        let eta = if record.front_face { self.refraction_index } else { 1.0/self.refraction_index };
+
+        let frame = Onb::from_tangent(record.shading_normal, record.dpdu);
+        let wo = frame.to_local(-Point::unit_vector(&ray_in.direction()));
+        let wi = frame.to_local(Point::unit_vector(&direction));
+
+        if (wi.z() > 0.0) != (direction.dot(record.normal) > 0.0) { return Color::default() }
+
+        let roughness = self.roughness.value(record.u, record.v, record.hit_location).x();
+        let ggx = Ggx::from_roughness(roughness, 0.0);
+
+        // the Fresnel term splits the energy between both sides
+        let value = if wi.z() > 0.0 {
+            microfacet_reflection(&ggx, wo, wi).map_or(0.0, |(h, value)| fresnel_dielectric(wo.dot(h), eta)*value)
+        } else {
+            microfacet_transmission(&ggx, wo, wi, eta).map_or(0.0, |(h, value)| (1.0 - fresnel_dielectric(wo.dot(h), eta))*value)
+        };
+
+        return ray_in.spectrum(Color::new(value, value, value));
+
+    }
+
+}
+
+//
+// Principled (Disney-style uber material)
+#[derive(Debug, Clone)]
+pub struct Principled {
+
+    pub base_color: Texture,
+    pub metallic: f32,
+    pub roughness: f32,
+    pub anisotropic: f32,
+    // 0.5 is a reflectance of 4% at normal incidence, i.e. an index of refraction of 1.5
+    pub specular: f32,
+    pub specular_tint: f32,
+    pub sheen: f32,
+    pub sheen_tint: f32,
+    pub clearcoat: f32,
+    pub clearcoat_gloss: f32,
+    pub transmission: f32,
+    pub subsurface: f32,
+
+}
+
+impl Principled {
+
+    pub fn new(base_color: Color, metallic: f32, roughness: f32) -> Material {
+        Material::Principled(Self { base_color: Texture::Solid(base_color), metallic, roughness, ..Default::default() })
+    }
+
+    pub fn refraction_index(&self) -> f32 {
+        let f0 = (0.08*self.specular).clamp(0.0, 0.999).sqrt();
+        (1.0 + f0) / (1.0 - f0)
+    }
+
+    // fraction of the light the specular microfacets let through to the diffuse lobe, 1 - F averaged
+    // over the visible normals the sampling picks from, on a fixed stratified set so eval stays deterministic
+    fn diffuse_fraction(ggx: &Ggx, wo: Point, eta: f32) -> f32 {
+
+        if ggx.is_smooth() { return 1.0 - fresnel_dielectric(wo.z(), eta) }
+
+        let n = DIFFUSE_STRATA;
+        let mut total = 0.0;
+
+        for a in 0..n {
+            for b in 0..n {
+                let h = ggx.sample_visible(wo, (a as f32 + 0.5) / n as f32, (b as f32 + 0.5) / n as f32);
+                total += 1.0 - fresnel_dielectric(wo.dot(h), eta);
+            }
+        }
+
+        return total / (n*n) as f32;
+
+    }
+
+    // hue of the base color, scaled so the brightest channel is one
+    fn tint(base: Color) -> Color {
+        let max_channel = base.x().max(base.y()).max(base.z());
+        if max_channel > 0.0 { base / max_channel } else { Color::new(1.0, 1.0, 1.0) }
+    }
+
+    fn scatter(&self, ray_in: &Ray, record: &HitRecord) -> Option<(Ray, Color, bool)> {
+
+        // the lobes are picked stochastically layer by layer, every lobe weight stays below
+        // one for a white base color so the material never creates energy:
+        // clearcoat -> metal -> dielectric specular -> transmission -> diffuse + sheen
+        let frame = Onb::from_tangent(record.shading_normal, record.dpdu);
+        let wo = frame.to_local(-Point::unit_vector(&ray_in.direction()));
+
+        if wo.z() <= 0.0 { return None }
+
+        let base = self.base_color.value(record.u, record.v, record.hit_location);
+        let tint = Principled::tint(base);
+        let white = Color::new(1.0, 1.0, 1.0);
+
+        let reflect = |ggx: &Ggx, tint: Color| {
+
+            let h = ggx.sample_visible(wo, Point::random_float(), Point::random_float());
+            let wi = Point::reflect(-wo, h);
+
+            if wi.z() <= 0.0 { return None }
+
+            Some((wi, tint * (ggx.g2(wo, wi) / ggx.g1(wo)), h))
+
+        };
+
+        let (wi, attenuation, reflected, specular) = 'lobe: {
+
+            // clearcoat, a fixed index of refraction 1.5 layer with its own gloss
+            if self.clearcoat > 0.0 && self.clearcoat * fresnel_dielectric(wo.z(), 1.5) > Point::random_float() {
+                let alpha = 0.1 + (0.001 - 0.1)*self.clearcoat_gloss.clamp(0.0, 1.0);
+                let ggx = Ggx::new(alpha, alpha);
+                let (wi, weight, _) = reflect(&ggx, white)?;
+                break 'lobe (wi, weight, true, ggx.is_smooth());
+            }
+
+            let ggx = Ggx::from_roughness(self.roughness, self.anisotropic);
+
+            // metal, tinted Schlick Fresnel with multiple scattering compensation
+            if self.metallic > Point::random_float() {
+                let (wi, weight, h) = reflect(&ggx, white)?;
+                let weight = weight * fresnel_schlick(wo.dot(h), base) * ggx.multiple_scattering(wo, base);
+                break 'lobe (wi, weight, true, ggx.is_smooth());
+            }
+
+            // dielectric specular on a microfacet normal, the Fresnel term picks reflection
+            let eta = if record.front_face { self.refraction_index() } else { 1.0/self.refraction_index() };
+            let h = if ggx.is_smooth() { Point::new(0.0, 0.0, 1.0) } else { ggx.sample_visible(wo, Point::random_float(), Point::random_float()) };
+
+            let specular_color = white + (tint - white)*self.specular_tint;
+
+            if fresnel_dielectric(wo.dot(h), eta) > Point::random_float() {
+                let wi = Point::reflect(-wo, h);
+                if wi.z() <= 0.0 { return None }
+                let weight = if ggx.is_smooth() { 1.0 } else { ggx.g2(wo, wi) / ggx.g1(wo) };
+                break 'lobe (wi, specular_color*weight, true, ggx.is_smooth());
+            }
+
+            // specular transmission through the same microfacet
+            if self.transmission > Point::random_float() {
+                let wi = Point::refract(-wo, h, 1.0/eta);
+                if wi.z() >= 0.0 { return None }
+                let weight = if ggx.is_smooth() { 1.0 } else { ggx.g2(wo, wi) / ggx.g1(wo) };
+                break 'lobe (wi, base*weight, false, ggx.is_smooth());
+            }
+
+            // diffuse, cosine sampled, with sheen at grazing angles
+            let mut wi = Point::new(0.0, 0.0, 1.0) + Point::random_on_sphere();
+            if wi.length_square() < 1e-12 { wi = Point::new(0.0, 0.0, 1.0) }
+            let wi = Point::unit_vector(&wi);
+
+            let half = Point::unit_vector(&(wo + wi));
+            let sheen = self.sheen * (1.0 - wi.dot(half).clamp(0.0, 1.0)).powi(5);
+            let sheen_color = white + (tint - white)*self.sheen_tint;
+            let weight = base + (sheen_color - base)*sheen;
+
+            // subsurface, approximated by diffuse transmission into the surface
+            if self.subsurface > Point::random_float() {
+                break 'lobe (Point::new(wi.x(), wi.y(), -wi.z()), weight, false, false);
+            }
+
+            (wi, weight, true, false)
+
+        };
+
+        let direction = keep_on_side(frame.to_world(wi), record.normal, reflected);
+        let ray_out = Ray::new(record.hit_location, direction);
+
+        return Some((ray_out, ray_in.spectrum(attenuation), specular));
+
+    }
+
+    fn eval(&self, ray_in: &Ray, record: &HitRecord, direction: Point) -> Color {
+
+        // every lobe weighted by the probability that the sampling reaches it
+        let frame = Onb::from_tangent(record.shading_normal, record.dpdu);
+        let wo = frame.to_local(-Point::unit_vector(&ray_in.direction()));
+        let wi = frame.to_local(Point::unit_vector(&direction));
+
+        if wo.z() <= 0.0 || (wi.z() > 0.0) != (direction.dot(record.normal) > 0.0) { return Color::default() }
+
+        let base = self.base_color.value(record.u, record.v, record.hit_location);
+        let tint = Principled::tint(base);
+        let white = Color::new(1.0, 1.0, 1.0);
+
+        let mut f = Color::default();
+        let mut remaining = 1.0;
+
+        // clearcoat
+        if self.clearcoat > 0.0 {
+            let coat = (self.clearcoat * fresnel_dielectric(wo.z(), 1.5)).min(1.0);
+            let alpha = 0.1 + (0.001 - 0.1)*self.clearcoat_gloss.clamp(0.0, 1.0);
+            if let Some((_, value)) = microfacet_reflection(&Ggx::new(alpha, alpha), wo, wi) {
+                f = f + white*(coat*value);
+            }
+            remaining *= 1.0 - coat;
+        }
+
+        let ggx = Ggx::from_roughness(self.roughness, self.anisotropic);
+        let reflection = microfacet_reflection(&ggx, wo, wi);
+
+        // metal
+        let metallic = self.metallic.clamp(0.0, 1.0);
+        if let Some((h, value)) = reflection {
+            f = f + fresnel_schlick(wo.dot(h), base) * ggx.multiple_scattering(wo, base) * (remaining*metallic*value);
+        }
+        remaining *= 1.0 - metallic;
+
+        // dielectric specular and transmission
+        let eta = if record.front_face { self.refraction_index() } else { 1.0/self.refraction_index() };
+        let specular_color = white + (tint - white)*self.specular_tint;
+        let transmission = self.transmission.clamp(0.0, 1.0);
+
+        if let Some((h, value)) = reflection {
+            f = f + specular_color*(remaining*fresnel_dielectric(wo.dot(h), eta)*value);
+        }
+        if let Some((h, value)) = microfacet_transmission(&ggx, wo, wi, eta) {
+            f = f + base*(remaining*transmission*(1.0 - fresnel_dielectric(wo.dot(h), eta))*value);
+        }
+
+        // diffuse, below the specular microfacets
+        remaining *= Principled::diffuse_fraction(&ggx, wo, eta) * (1.0 - transmission);
+
+        // the subsurface lobe is the diffuse one mirrored into the surface
+        let subsurface = self.subsurface.clamp(0.0, 1.0);
+        let (side, wd) = if wi.z() > 0.0 { (1.0 - subsurface, wi) } else { (subsurface, Point::new(wi.x(), wi.y(), -wi.z())) };
+
+        let half = Point::unit_vector(&(wo + wd));
+        let sheen = self.sheen * (1.0 - wd.dot(half).clamp(0.0, 1.0)).powi(5);
+        let sheen_color = white + (tint - white)*self.sheen_tint;
+        let weight = base + (sheen_color - base)*sheen;
+
+        f = f + weight*(remaining*side*wd.z() / PI);
+
+        return ray_in.spectrum(f);
+
+    }
+
+}
+
+impl Default for Principled {
+
+    fn default() -> Self {
+        Self {
+            base_color: Texture::Solid(Color::new(0.8, 0.8, 0.8)),
+            metallic: 0.0,
+            roughness: 0.5,
+            anisotropic: 0.0,
+            specular: 0.5,
+            specular_tint: 0.0,
+            sheen: 0.0,
+            sheen_tint: 0.5,
+            clearcoat: 0.0,
+            clearcoat_gloss: 1.0,
+            transmission: 0.0,
+            subsurface: 0.0,
+        }
+    }
+
+}
+
+impl From<Principled> for Material {
+
+    fn from(principled: Principled) -> Self {
+        Material::Principled(principled)
+    }
+
+}
+
+//
+// Mapped (normal or bump mapped material)
+#[derive(Debug, Clone)]
+pub struct Mapped {
+
+    base: Arc<Material>,
+    map: NormalMap,
+
+}
+
+impl Mapped {
+
+    pub fn new(base: Material, map: NormalMap) -> Material {
+        Material::Mapped(Self { base: Arc::new(base), map })
+    }
+
+    fn scatter(&self, ray_in: &Ray, record: &HitRecord) -> Option<(Ray, Color, bool)> {
+
+        // only the shading frame is perturbed, the geometric normal is kept for the side checks
+        let mut record = record.clone();
+        record.shading_normal = self.map.shading_normal(&record);
+
+        return self.base.scatter(ray_in, &record);
+
+    }
+
+    fn eval(&self, ray_in: &Ray, record: &HitRecord, direction: Point) -> Color {
+
+        let mut record = record.clone();
+        record.shading_normal = self.map.shading_normal(&record);
+
+        return self.base.eval(ray_in, &record, direction);
+
+    }
+
+}
+
+//
+// Layered (dielectric coat over any base material)
+#[derive(Debug, Clone)]
+pub struct Layered {
+
+    base: Arc<Material>,
+    refraction_index: f32,
+    roughness: Texture,
+    // optical depth of the coat at normal incidence, per channel
+    absorption: Color,
+
+}
+
+// bounces inside the coat after which the remaining energy is dropped
+const MAX_LAYER_BOUNCES: usize = 32;
+
+impl Layered {
+
+    pub fn new(base: Material, refraction_index: f32, roughness: f32) -> Material {
+        Layered::tinted(base, refraction_index, roughness, Color::new(1.0, 1.0, 1.0))
+    }
+
+    // coat that transmits the given color when crossed once at normal incidence
+    pub fn tinted(base: Material, refraction_index: f32, roughness: f32, color: Color) -> Material {
+        Layered::textured(base, refraction_index, Texture::Solid(Color::new(roughness, roughness, roughness)), color)
+    }
+
+    pub fn textured(base: Material, refraction_index: f32, roughness: Texture, color: Color) -> Material {
+
+        let depth = |c: f32| -c.clamp(1e-6, 1.0).ln();
+        let absorption = Color::new(depth(color.x()), depth(color.y()), depth(color.z()));
+
+        Material::Layered(Self { base: Arc::new(base), refraction_index, roughness, absorption })
+
+    }
+
+    // crosses the coat interface from the side of wo (z > 0 in the given orientation),
+    // returns the new direction and the walk weight, Walter et al. 2007 like RoughDielectric
+    fn interface(ggx: &Ggx, wo: Point, eta: f32) -> Option<(Point, f32)> {
+
+        let h = if ggx.is_smooth() {
+            Point::new(0.0, 0.0, 1.0)
+        } else {
+            ggx.sample_visible(wo, Point::random_float(), Point::random_float())
+        };
+
+        let reflect = fresnel_dielectric(wo.dot(h), eta) > Point::random_float();
+        let wi = if reflect { Point::reflect(-wo, h) } else { Point::refract(-wo, h, 1.0/eta) };
+
+        if reflect == (wi.z() <= 0.0) { return None }
+
+        let weight = if ggx.is_smooth() { 1.0 } else { ggx.g2(wo, wi) / ggx.g1(wo) };
+
+        return Some((Point::unit_vector(&wi), weight));
+
+    }
+
+    fn scatter(&self, ray_in: &Ray, record: &HitRecord) -> Option<(Ray, Color, bool)> {
+
+        // random walk between the coat interface and the base, in the local shading frame;
+        // the coat is infinitely thin, only the absorption depends on the path length inside
+        let frame = Onb::from_tangent(record.shading_normal, record.dpdu);
+        let wo = frame.to_local(-Point::unit_vector(&ray_in.direction()));
+
+        if wo.z() <= 0.0 { return None }
+
+        let roughness = self.roughness.value(record.u, record.v, record.hit_location).x();
+        let ggx = Ggx::from_roughness(roughness, 0.0);
+
+        let mirror = |w: Point| Point::new(w.x(), w.y(), -w.z());
+        let crossing = |w: Point| ray_in.spectrum((-self.absorption / w.z().abs().max(1e-4)).exp());
+
+        // reflection on the coat, or refraction into it
+        let (mut w, weight) = Layered::interface(&ggx, wo, self.refraction_index)?;
+        let mut attenuation = Color::new(weight, weight, weight);
+        let mut wavelengths = ray_in.wavelengths();
+
+        // the walk is specular as long as every event on it is
+        let mut specular = ggx.is_smooth();
+
+        if w.z() > 0.0 {
+            let ray_out = Ray::new(record.hit_location, keep_on_side(frame.to_world(w), record.normal, true));
+            return Some((ray_out, attenuation, specular));
+        }
+
+        for _ in 0..MAX_LAYER_BOUNCES {
+
+            attenuation = attenuation * crossing(w);
+
+            // the base sees the ray refracted by the coat
+            let ray_base = Ray::new(record.hit_location - frame.to_world(w), frame.to_world(w)).set_wavelengths(wavelengths);
+            let (ray_base_out, base_attenuation, base_specular) = self.base.scatter(&ray_base, record)?;
+
+            attenuation = attenuation * base_attenuation;
+            specular = specular && base_specular;
+            wavelengths = ray_base_out.wavelengths().or(wavelengths);
+            w = Point::unit_vector(&frame.to_local(ray_base_out.direction()));
+
+            // the base transmits, the coat only covers its upper side
+            if w.z() <= 0.0 {
+                let ray_out = Ray::new(record.hit_location, keep_on_side(frame.to_world(w), record.normal, false));
+                return Some((ray_out.set_wavelengths(wavelengths), attenuation, specular));
+            }
+
+            attenuation = attenuation * crossing(w);
+
+            // the underside of the coat, seen mirrored so that the walk stays above the interface
+            let (w_next, weight) = Layered::interface(&ggx, mirror(-w), 1.0/self.refraction_index)?;
+            attenuation = attenuation * weight;
+            w = mirror(w_next);
+
+            if w.z() > 0.0 {
+                let ray_out = Ray::new(record.hit_location, keep_on_side(frame.to_world(w), record.normal, true));
+                return Some((ray_out.set_wavelengths(wavelengths), attenuation, specular));
+            }
+
+        }
+
+        return None;
+
+    }
+
+    fn eval(&self, ray_in: &Ray, record: &HitRecord, direction: Point) -> Color {
+
+        // the same random walk as the sampling, connected to the light at every base event
+        // through a smooth coat (position-free Monte Carlo, Guo et al. 2018), plus the glossy
+        // reflection on the coat itself
+        let frame = Onb::from_tangent(record.shading_normal, record.dpdu);
+        let wo = frame.to_local(-Point::unit_vector(&ray_in.direction()));
+        let wi = frame.to_local(Point::unit_vector(&direction));
+
+        // the coat only covers the upper side, light transmitted by the base is left out
+        if wo.z() <= 0.0 || wi.z() <= 0.0 || direction.dot(record.normal) <= 0.0 { return Color::default() }
+
+        let roughness = self.roughness.value(record.u, record.v, record.hit_location).x();
+        let ggx = Ggx::from_roughness(roughness, 0.0);
+
+        let mirror = |w: Point| Point::new(w.x(), w.y(), -w.z());
+        let crossing = |w: Point| ray_in.spectrum((-self.absorption / w.z().abs().max(1e-4)).exp());
+
+        let mut f = match microfacet_reflection(&ggx, wo, wi) {
+            Some((h, value)) => {
+                let value = fresnel_dielectric(wo.dot(h), self.refraction_index)*value;
+                ray_in.spectrum(Color::new(value, value, value))
+            }
+            None => Color::default(),
+        };
+
+        // the light direction inside the coat, and what the coat lets through towards it
+        let wi_inside = -Point::refract(-wi, Point::new(0.0, 0.0, 1.0), 1.0/self.refraction_index);
+        let eta_square = self.refraction_index*self.refraction_index;
+        let exit = crossing(wi_inside) * ((1.0 - fresnel_dielectric(wi.z(), self.refraction_index)) * wi.z() / (wi_inside.z().max(1e-4)*eta_square));
+
+        let Some((mut w, weight)) = Layered::interface(&ggx, wo, self.refraction_index) else { return f };
+        let mut attenuation = Color::new(weight, weight, weight);
+        let mut wavelengths = ray_in.wavelengths();
+
+        if w.z() > 0.0 { return f }
+
+        for _ in 0..MAX_LAYER_BOUNCES {
+
+            attenuation = attenuation * crossing(w);
+
+            let ray_base = Ray::new(record.hit_location - frame.to_world(w), frame.to_world(w)).set_wavelengths(wavelengths);
+
+            f = f + attenuation * self.base.eval(&ray_base, record, frame.to_world(wi_inside)) * exit;
+
+            let Some((ray_base_out, base_attenuation, _)) = self.base.scatter(&ray_base, record) else { return f };
+
+            attenuation = attenuation * base_attenuation;
+            wavelengths = ray_base_out.wavelengths().or(wavelengths);
+            w = Point::unit_vector(&frame.to_local(ray_base_out.direction()));
+
+            if w.z() <= 0.0 { return f }
+
+            attenuation = attenuation * crossing(w);
+
+            let Some((w_next, weight)) = Layered::interface(&ggx, mirror(-w), 1.0/self.refraction_index) else { return f };
+            attenuation = attenuation * weight;
+            w = mirror(w_next);
+
+            if w.z() > 0.0 { return f }
+
+        }
+
+        return f;
+
+    }
+
+}
+
+//
+// Subsurface (random walk in a scattering interior)
+#[derive(Debug, Clone)]
+pub struct Subsurface {
+
+    surface: Dielectric,
+    medium: Medium,
+
+}
+
+impl Subsurface {
+
+    // color is the overall color of the object seen from outside, and the mean free path the
+    // average distance light travels inside between two events, per channel
+    pub fn new(color: Color, mean_free_path: Color, refraction_index: f32) -> Material {
+
+        let albedo = Color::new(Subsurface::single_albedo(color.x()), Subsurface::single_albedo(color.y()), Subsurface::single_albedo(color.z()));
+        let sigma_t = Color::new(1.0 / mean_free_path.x(), 1.0 / mean_free_path.y(), 1.0 / mean_free_path.z());
+
+        let surface = Dielectric { refraction_index, absorption: Color::default(), dispersion: None, film: None };
+
+        Material::Subsurface(Self { surface, medium: Homogeneous::new(sigma_t, albedo, Phase::Isotropic) })
+
+    }
+
+    // single scattering albedo giving the multiple scattering albedo of a semi-infinite slab,
+    // van de Hulst's inversion as fitted by Christensen and Burley 2015
+    fn single_albedo(color: f32) -> f32 {
+        let a = color.clamp(0.0, 1.0);
+        let s = 4.09712 + 4.20863*a - (9.59217 + 41.6808*a + 17.7126*a*a).sqrt();
+        (1.0 - s*s).clamp(0.0, 1.0)
+    }
+
+    fn scatter(&self, ray_in: &Ray, record: &HitRecord) -> Option<(Ray, Color, bool)> {
+
+        // the boundary is smooth glass, the color comes from the walk inside
+        return self.surface.scatter(ray_in, record);
+
+    }
+
+}
+
+//
+// Volume (invisible boundary of a participating medium)
+#[derive(Debug, Clone)]
+pub struct Volume {
+
+    medium: Medium,
+
+}
+
+impl Volume {
+
+    pub fn new(medium: Medium) -> Material {
+        Material::Volume(Self { medium })
+    }
+
+    fn scatter(&self, ray_in: &Ray, record: &HitRecord) -> Option<(Ray, Color, bool)> {
+
+        // index matched, the ray carries on unchanged into or out of the medium
+        let ray_out = Ray::new(record.hit_location, ray_in.direction());
+
+        return Some((ray_out, Color::new(1.0, 1.0, 1.0), true));
+
+    }
+
+}
+
+//
+// Mix (blend of two materials)
+#[derive(Debug, Clone)]
+pub struct Mix {
+
+    first: Arc<Material>,
+    second: Arc<Material>,
+    // weight of the second material, read from the red channel
+    factor: Texture,
+
+}
+
+impl Mix {
+
+    pub fn new(first: Material, second: Material, factor: f32) -> Material {
+        Mix::textured(first, second, Texture::Solid(Color::new(factor, factor, factor)))
+    }
+
+    // e.g. a mask texture for dirt, rust or worn paint
+    pub fn textured(first: Material, second: Material, factor: Texture) -> Material {
+        Material::Mix(Self { first: Arc::new(first), second: Arc::new(second), factor })
+    }
+
+    fn scatter(&self, ray_in: &Ray, record: &HitRecord) -> Option<(Ray, Color, bool)> {
+
+        // the material is picked with the probability of its weight, so the sample of the
+        // picked material is an unbiased sample of the weighted sum, with the same weight
+        let factor = self.factor.value(record.u, record.v, record.hit_location).x().clamp(0.0, 1.0);
+
+        if factor > Point::random_float() {
+            return self.second.scatter(ray_in, record);
+        }
+
+        return self.first.scatter(ray_in, record);
+
+    }
+
+    fn eval(&self, ray_in: &Ray, record: &HitRecord, direction: Point) -> Color {
+
+        let factor = self.factor.value(record.u, record.v, record.hit_location).x().clamp(0.0, 1.0);
+
+        return self.first.eval(ray_in, record, direction)*(1.0 - factor) + self.second.eval(ray_in, record, direction)*factor;
+
+    }
+
+}
+
+//
+// Cutout (alpha masked material)
+#[derive(Debug, Clone)]
+pub struct Cutout {
+
+    base: Arc<Material>,
+    // opacity in the red channel, 0 is a hole
+    opacity: Texture,
+
+}
+
+impl Cutout {
+
+    pub fn new(base: Material, opacity: f32) -> Material {
+        Cutout::textured(base, Texture::Solid(Color::new(opacity, opacity, opacity)))
+    }
+
+    // e.g. a black and white mask for leaves, fences or decals
+    pub fn textured(base: Material, opacity: Texture) -> Material {
+        Material::Cutout(Self { base: Arc::new(base), opacity })
+    }
+
+    fn scatter(&self, ray_in: &Ray, record: &HitRecord) -> Option<(Ray, Color, bool)> {
+
+        // the holes never get here, the hittable list already skipped them
+        return self.base.scatter(ray_in, record);
+
+    }
+
+}
+
+//
+// DiffuseLight (emitter on the front side, absorbs everything)
+#[derive(Debug, Clone)]
+pub struct DiffuseLight {
+
+    radiance: Color,
+
+}
+
+impl DiffuseLight {
+
+    // objects with this material are sampled as area lights when added to the world
+    pub fn new(radiance: Color) -> Material {
+        Material::DiffuseLight(Self { radiance })
+    }
+
+}
+
+//
+// tests
+#[test]
+fn test_mapped_stays_above_surface(){
+
+    use crate::texture::Texture;
+
+    // a normal map tilted to grazing, reflections must not leak below the surface
+    let map = NormalMap::Tangent(Texture::Solid(Color::new(1.0, 0.5, 0.52)));
+    let metal = Mapped::new(Metal::new(Color::new(0.9, 0.9, 0.9), 0.0), map.clone());
+    let diffuse = Mapped::new(Lambertian::new(Color::new(0.9, 0.9, 0.9)), map);
+
+    let normal = Point::new(0.0, 0.0, 1.0);
+    let ray = Ray::new(Point::new(-1.0, 0.0, 1.0), Point::new(1.0, 0.0, -1.0));
+    let record = HitRecord::new(Point::default(), normal, 1.0, &ray,
+        0.5, 0.5, Point::new(1.0, 0.0, 0.0), Point::new(0.0, 1.0, 0.0));
+
+    for _ in 0..100 {
+
+        if let Some((ray_out, _, _)) = metal.scatter(&ray, &record) {
+            assert!(ray_out.direction().dot(normal) >= 0.0);
+        }
+
+        let (ray_out, _, _) = diffuse.scatter(&ray, &record).expect("Diffuse always scatters.");
+        assert!(ray_out.direction().dot(normal) >= 0.0);
+
+    }
+
+}
+
+#[test]
+fn test_metal_energy(){
+
+    // white furnace: with multiple scattering compensation a white conductor reflects (almost) all energy
+    let normal = Point::new(0.0, 0.0, 1.0);
+    let record = |ray: &Ray| HitRecord::new(Point::default(), normal, 1.0, ray,
+        0.5, 0.5, Point::new(1.0, 0.0, 0.0), Point::new(0.0, 1.0, 0.0));
+
+    for roughness in [0.0, 0.3, 0.7, 1.0] {
+        for anisotropy in [0.0, 0.8] {
+
+            let metal = Metal::textured(Fresnel::Schlick(Color::new(1.0, 1.0, 1.0)), 
+                Texture::Solid(Color::new(roughness, roughness, roughness)), anisotropy);
+
+            let ray = Ray::new(Point::new(-1.0, 0.2, 1.0), Point::new(1.0, -0.2, -1.0));
+            let record = record(&ray);
+
+            let n = 20000;
+            let mut total = 0.0;
+
+            for _ in 0..n {
+                if let Some((ray_out, attenuation, _)) = metal.scatter(&ray, &record) {
+                    assert!(ray_out.direction().dot(normal) >= 0.0);
+                    total += attenuation.x();
+                }
+            }
+
+            let average = total / (n as f32);
+            // the compensation is only approximate for anisotropic roughness, which loses some energy
+            let lower = if anisotropy > 0.0 { 0.7 } else { 0.9 };
+            assert!(average >= lower, "roughness {} anisotropy {} loses energy: {}", roughness, anisotropy, average);
+            assert!(average <= 1.03, "roughness {} anisotropy {} gains energy: {}", roughness, anisotropy, average);
+
+        }
+    }
+
+    // measured gold reflects red more than blue at normal incidence
+    let gold = Metal::gold(0.0);
+    let ray = Ray::new(Point::new(0.0, 0.0, 1.0), Point::new(0.0, 0.0, -1.0));
+    let (_, attenuation, _) = gold.scatter(&ray, &record(&ray)).expect("Smooth gold always reflects.");
+    assert!(attenuation.x() > 0.9 && attenuation.z() < 0.5);
+
+}
+
+#[test]
+fn test_rough_dielectric(){
+
+    let normal = Point::new(0.0, 0.0, 1.0);
+    let record = |ray: &Ray| HitRecord::new(Point::default(), normal, 1.0, ray,
+        0.5, 0.5, Point::new(1.0, 0.0, 0.0), Point::new(0.0, 1.0, 0.0));
+
+    for roughness in [0.0, 0.2, 0.6] {
+
+        let glass = RoughDielectric::new(1.5, roughness);
+
+        // at normal incidence about 4% is reflected and the rest refracted
+        let ray = Ray::new(Point::new(0.0, 0.0, 1.0), Point::new(0.0, 0.0, -1.0));
+        let record = record(&ray);
+
+        let n = 20000;
+        let mut reflected = 0.0;
+        let mut total = 0.0;
+
+        for _ in 0..n {
+            if let Some((ray_out, attenuation, _)) = glass.scatter(&ray, &record) {
+                if ray_out.direction().dot(normal) > 0.0 { reflected += attenuation.x() }
+                total += attenuation.x();
+            }
+        }
+
+        let reflected = reflected / (n as f32);
+        let total = total / (n as f32);
+
+        assert!(total <= 1.0 + 1e-3);
+        assert!(total >= 0.9);
+        assert!((reflected - 0.04).abs() < 0.02, "reflected {} for roughness {}", reflected, roughness);
+
+    }
+
+    // from the inside beyond the critical angle everything is reflected back inside
+    let glass = RoughDielectric::new(1.5, 0.0);
+    let ray = Ray::new(Point::new(-1.0, 0.0, -1.0), Point::new(1.0, 0.0, 1.0));
+    let record = record(&ray);
+    assert!(!record.front_face);
+    for _ in 0..100 {
+        let (ray_out, _, _) = glass.scatter(&ray, &record).expect("Smooth glass always scatters.");
+        assert!(ray_out.direction().z() < 0.0);
+    }
+
+}
+
+#[test]
+fn test_principled_white_furnace(){
+
+    // a white material under uniform white light must reflect (almost) all of it and never more
+    let white = Texture::Solid(Color::new(1.0, 1.0, 1.0));
+    let normal = Point::new(0.0, 0.0, 1.0);
+
+    let configurations = [
+        Principled { base_color: white.clone(), ..Default::default() },
+        Principled { base_color: white.clone(), roughness: 0.0, ..Default::default() },
+        Principled { base_color: white.clone(), roughness: 1.0, ..Default::default() },
+        Principled { base_color: white.clone(), metallic: 1.0, roughness: 0.3, ..Default::default() },
+        Principled { base_color: white.clone(), metallic: 1.0, roughness: 0.9, anisotropic: 0.5, ..Default::default() },
+        Principled { base_color: white.clone(), clearcoat: 1.0, sheen: 1.0, specular_tint: 1.0, ..Default::default() },
+        Principled { base_color: white.clone(), metallic: 0.5, clearcoat: 1.0, clearcoat_gloss: 0.2, ..Default::default() },
+        Principled { base_color: white.clone(), transmission: 1.0, roughness: 0.0, ..Default::default() },
+        Principled { base_color: white.clone(), transmission: 1.0, roughness: 0.3, ..Default::default() },
+        Principled { base_color: white.clone(), subsurface: 1.0, ..Default::default() },
+    ];
+
+    let anisotropic: Vec<bool> = configurations.iter().map(|p| p.anisotropic > 0.0).collect();
+
+    for (i, principled) in configurations.into_iter().enumerate() {
+
+        let material: Material = principled.into();
+
+        for direction in [Point::new(0.0, 0.0, -1.0), Point::new(1.0, 0.3, -1.0), Point::new(3.0, 0.0, -1.0)] {
+
+            let ray = Ray::new(Point::default() - direction, direction);
+            let record = HitRecord::new(Point::default(), normal, 1.0, &ray,
+                0.5, 0.5, Point::new(1.0, 0.0, 0.0), Point::new(0.0, 1.0, 0.0));
+
+            let n = 20000;
+            let total: Color = (0..n).filter_map(|_| material.scatter(&ray, &record))
+                .map(|(_, attenuation, _)| attenuation)
+                .sum();
+            let average = total / (n as f32);
+
+            // the multiple scattering compensation is only approximate for anisotropic roughness
+            let tolerance = if anisotropic[i] { 0.05 } else { 0.02 };
+
+            for channel in [average.x(), average.y(), average.z()] {
+                assert!(channel <= 1.0 + tolerance, "configuration {} gains energy: {}", i, channel);
+                assert!(channel >= 0.9, "configuration {} loses energy: {}", i, channel);
+            }
+
+        }
+
+    }
+
+}
+
+#[test]
+fn test_absorbing_dielectric(){
+
+    let normal = Point::new(0.0, 0.0, 1.0);
+    let glass = Dielectric::tinted(1.5, Color::new(0.5, 0.8, 1.0), 2.0);
+
+    // entering the glass is not attenuated
+    let ray = Ray::new(Point::new(0.0, 0.0, 1.0), Point::new(0.0, 0.0, -1.0));
+    let record = HitRecord::new(Point::default(), normal, 1.0, &ray,
+        0.5, 0.5, Point::new(1.0, 0.0, 0.0), Point::new(0.0, 1.0, 0.0));
+    let (_, attenuation, _) = glass.scatter(&ray, &record).expect("Glass always scatters.");
+    assert_eq!(attenuation, Color::new(1.0, 1.0, 1.0));
+
+    // leaving after 2 units inside gives the tint color, 4 units gives it squared
+    let ray = Ray::new(Point::new(0.0, 0.0, -2.0), Point::new(0.0, 0.0, 1.0));
+    let record = HitRecord::new(Point::default(), normal, 2.0, &ray,
+        0.5, 0.5, Point::new(1.0, 0.0, 0.0), Point::new(0.0, 1.0, 0.0));
+    let (_, attenuation, _) = glass.scatter(&ray, &record).expect("Glass always scatters.");
+    assert_relative_eq!(attenuation.x(), 0.5, epsilon = 1e-6);
+    assert_relative_eq!(attenuation.y(), 0.8, epsilon = 1e-6);
+    assert_relative_eq!(attenuation.z(), 1.0);
+
+    let ray = Ray::new(Point::new(0.0, 0.0, -4.0), Point::new(0.0, 0.0, 2.0));
+    let (_, attenuation, _) = glass.scatter(&ray, &record).expect("Glass always scatters.");
+    assert_relative_eq!(attenuation.x(), 0.25, epsilon = 1e-6);
+    assert_relative_eq!(attenuation.y(), 0.64, epsilon = 1e-6);
+    assert_relative_eq!(attenuation.z(), 1.0);
+
+    // no distance, or a negative one, still gives a finite absorption
+    for distance in [0.0, -1.0] {
+        let (_, attenuation, _) = Dielectric::tinted(1.5, Color::new(0.5, 0.8, 1.0), distance)
+            .scatter(&ray, &record).expect("Glass always scatters.");
+        assert!(attenuation.x().is_finite() && attenuation.x() >= 0.0 && attenuation.x() < 1e-3);
+        assert_relative_eq!(attenuation.z(), 1.0);
+    }
+
+}
+
+#[test]
+fn test_dispersive_dielectric(){
+
+    let normal = Point::new(0.0, 0.0, 1.0);
+    let diamond = Dielectric::dispersive(Dispersion::diamond());
+
+    // rgb rays are not affected
+    let ray = Ray::new(Point::new(-1.0, 0.0, 1.0), Point::new(1.0, 0.0, -1.0));
+    let record = HitRecord::new(Point::default(), normal, 1.0, &ray,
+        0.5, 0.5, Point::new(1.0, 0.0, 0.0), Point::new(0.0, 1.0, 0.0));
+    let (ray_out, attenuation, _) = diamond.scatter(&ray, &record).expect("Glass always scatters.");
+    assert_eq!(attenuation, Color::new(1.0, 1.0, 1.0));
+    assert!(ray_out.wavelengths().is_none());
+
+    // spectral rays keep only the hero wavelength, once
+    let refracted = |u: f32| {
+        let ray = ray.clone().set_wavelengths(Some(Wavelengths::sample(u)));
+        loop {
+            let (ray_out, attenuation, _) = diamond.scatter(&ray, &record).expect("Glass always scatters.");
+            assert!(ray_out.wavelengths().expect("Spectral ray.").is_terminated());
+            assert_relative_eq!(attenuation.x(), 3.0, epsilon = 1e-4);
+            assert_eq!(attenuation.y(), 0.0);
+            if ray_out.direction().z() < 0.0 { return Point::unit_vector(&ray_out.direction()) }
+        }
+    };
+
+    let violet = refracted(0.05);
+    let red = refracted(0.95);
+
+    // blue bends more towards the normal
+    assert!(violet.x() < red.x());
+
+    let ray = ray.clone().set_wavelengths(Some(Wavelengths::sample(0.5).terminate_secondary()));
+    let (_, attenuation, _) = diamond.scatter(&ray, &record).expect("Glass always scatters.");
+    assert_relative_eq!(attenuation.x(), 1.0, epsilon = 1e-4);
+
+}
+
+#[test]
+fn test_thin_film_materials(){
+
+    let normal = Point::new(0.0, 0.0, 1.0);
+    let ray = Ray::new(Point::new(-0.5, 0.0, 1.0), Point::new(0.5, 0.0, -1.0));
+    let record = HitRecord::new(Point::default(), normal, 1.0, &ray,
+        0.5, 0.5, Point::new(1.0, 0.0, 0.0), Point::new(0.0, 1.0, 0.0));
+
+    // a soap bubble passes light straight through and reflects a colored fraction, without losing energy
+    let bubble = Dielectric::filmed(1.0, ThinFilm::new(450.0, 1.33));
+
+    let n = 20000;
+    let mut reflected = Color::default();
+    let mut total = Color::default();
+
+    for _ in 0..n {
+        let (ray_out, attenuation, _) = bubble.scatter(&ray, &record).expect("Glass always scatters.");
+        if ray_out.direction().z() > 0.0 {
+            reflected = reflected + attenuation;
+        } else {
+            assert_relative_eq!((Point::unit_vector(&ray_out.direction()) - Point::unit_vector(&ray.direction())).length(), 0.0, epsilon = 1e-5);
+        }
+        total = total + attenuation;
+    }
+
+    let reflected = reflected / (n as f32);
+    let total = total / (n as f32);
+
+    for channel in [total.x(), total.y(), total.z()] {
+        assert_relative_eq!(channel, 1.0, epsilon = 0.05);
+    }
+    assert!((reflected.x() - reflected.z()).abs() > 0.02);
+
+    // a film of zero thickness leaves the metal unchanged, a real film tints it
+    let roughness = Texture::Solid(Color::default());
+    let plain = Metal::textured(Fresnel::Conductor(Color::new(1.5, 1.5, 1.5), Color::new(3.0, 3.0, 3.0)), roughness.clone(), 0.0);
+    let bare = Metal::filmed(Fresnel::Conductor(Color::new(1.5, 1.5, 1.5), Color::new(3.0, 3.0, 3.0)), roughness.clone(), 0.0, ThinFilm::new(0.0, 2.4));
+    let anodised = Metal::filmed(Fresnel::Conductor(Color::new(1.5, 1.5, 1.5), Color::new(3.0, 3.0, 3.0)), roughness, 0.0, ThinFilm::new(80.0, 2.4));
+
+    let (_, a, _) = plain.scatter(&ray, &record).expect("Smooth metal always reflects.");
+    let (_, b, _) = bare.scatter(&ray, &record).expect("Smooth metal always reflects.");
+    let (_, c, _) = anodised.scatter(&ray, &record).expect("Smooth metal always reflects.");
+
+    assert_relative_eq!((a - b).length(), 0.0, epsilon = 1e-4);
+    assert!((c.x() - c.z()).abs() > 0.05);
+
+}
+
+#[test]
+fn test_layered(){
+
+    let normal = Point::new(0.0, 0.0, 1.0);
+    let white = Color::new(1.0, 1.0, 1.0);
+
+    for direction in [Point::new(0.0, 0.0, -1.0), Point::new(1.0, 0.3, -1.0), Point::new(3.0, 0.0, -1.0)] {
+
+        let ray = Ray::new(Point::default() - direction, direction);
+        let record = HitRecord::new(Point::default(), normal, 1.0, &ray,
+            0.5, 0.5, Point::new(1.0, 0.0, 0.0), Point::new(0.0, 1.0, 0.0));
+
+        // a clear coat over a white diffuse base keeps (almost) all the energy
+        for roughness in [0.0, 0.3] {
+
+            let coated = Layered::new(Lambertian::new(white), 1.5, roughness);
+
+            let n = 20000;
+            let total: Color = (0..n).filter_map(|_| coated.scatter(&ray, &record))
+                .map(|(ray_out, attenuation, _)| {
+                    assert!(ray_out.direction().dot(normal) >= 0.0);
+                    attenuation
+                })
+                .sum();
+            let average = total / (n as f32);
+
+            assert!(average.x() <= 1.02 && average.x() >= 0.9, "coat loses or gains energy: {}", average.x());
+
+        }
+
+        // a tinted coat absorbs on the way in and out
+        let tinted = Layered::tinted(Lambertian::new(white), 1.5, 0.0, Color::new(0.5, 1.0, 1.0));
+        let n = 20000;
+        let total: Color = (0..n).filter_map(|_| tinted.scatter(&ray, &record)).map(|(_, attenuation, _)| attenuation).sum();
+        let average = total / (n as f32);
+        assert!(average.x() < 0.5*average.y());
+
+    }
+
+    // over a black base only the Fresnel reflection of the coat remains
+    let ray = Ray::new(Point::new(0.0, 0.0, 1.0), Point::new(0.0, 0.0, -1.0));
+    let record = HitRecord::new(Point::default(), normal, 1.0, &ray,
+        0.5, 0.5, Point::new(1.0, 0.0, 0.0), Point::new(0.0, 1.0, 0.0));
+    let coated = Layered::new(Lambertian::new(Color::default()), 1.5, 0.0);
+
+    let n = 20000;
+    let total: Color = (0..n).filter_map(|_| coated.scatter(&ray, &record)).map(|(_, attenuation, _)| attenuation).sum();
+    assert_relative_eq!(total.x() / (n as f32), 0.04, epsilon = 0.01);
+
+}
+
+#[test]
+fn test_subsurface(){
+
+    // the albedo inversion keeps black and white, and is monotonic in between
+    assert_relative_eq!(Subsurface::single_albedo(0.0), 0.0, epsilon = 1e-4);
+    assert!(Subsurface::single_albedo(1.0) > 0.998);
+
+    let albedos: Vec<f32> = (0..=10).map(|i| Subsurface::single_albedo(i as f32 / 10.0)).collect();
+    assert!(albedos.windows(2).all(|w| w[0] < w[1]));
+
+    // a bright color needs a single scattering albedo close to one
+    assert!(Subsurface::single_albedo(0.8) > 0.95);
+
+    let wax = Subsurface::new(Color::new(0.9, 0.8, 0.6), Color::new(0.2, 0.1, 0.05), 1.4);
+    assert!(wax.interior().is_some());
+    assert!(Lambertian::new(Color::new(0.9, 0.8, 0.6)).interior().is_none());
+
+}
+
+#[test]
+fn test_mix(){
+
+    let normal = Point::new(0.0, 0.0, 1.0);
+    let ray = Ray::new(Point::new(0.0, 0.0, 1.0), Point::new(0.0, 0.0, -1.0));
+    let record = HitRecord::new(Point::default(), normal, 1.0, &ray,
+        0.25, 0.5, Point::new(1.0, 0.0, 0.0), Point::new(0.0, 1.0, 0.0));
+
+    let black = Lambertian::new(Color::new(0.0, 0.0, 0.0));
+    let white = Lambertian::new(Color::new(1.0, 1.0, 1.0));
+
+    // the average is the weighted sum of the two materials
+    let mix = Mix::new(black.clone(), white.clone(), 0.3);
+    let n = 20000;
+    let total: Color = (0..n).filter_map(|_| mix.scatter(&ray, &record)).map(|(_, attenuation, _)| attenuation).sum();
+    assert_relative_eq!(total.x() / (n as f32), 0.3, epsilon = 0.02);
+
+    // a mask picks the material per texel, white on the left half here
+    let mask = crate::texture::Image::new(2, 1, vec![Color::new(1.0, 1.0, 1.0), Color::new(0.0, 0.0, 0.0)]);
+    let masked = Mix::textured(black, white, mask);
+    for _ in 0..100 {
+        let (_, attenuation, _) = masked.scatter(&ray, &record).expect("Diffuse always scatters.");
+        assert_eq!(attenuation, Color::new(1.0, 1.0, 1.0));
+    }
+
+}
+
+#[test]
+fn test_oren_nayar(){
+
+    let normal = Point::new(0.0, 0.0, 1.0);
+    let white = Color::new(1.0, 1.0, 1.0);
+    let record = |ray: &Ray| HitRecord::new(Point::default(), normal, 1.0, ray,
+        0.5, 0.5, Point::new(1.0, 0.0, 0.0), Point::new(0.0, 1.0, 0.0));
+
+    // without roughness it is Lambertian
+    let smooth = OrenNayar::new(Color::new(0.5, 0.6, 0.7), 0.0);
+    let ray = Ray::new(Point::new(-1.0, 0.0, 1.0), Point::new(1.0, 0.0, -1.0));
+    for _ in 0..100 {
+        let (ray_out, attenuation, _) = smooth.scatter(&ray, &record(&ray)).expect("Diffuse always scatters.");
+        assert!(ray_out.direction().dot(normal) >= 0.0);
+        assert_relative_eq!((attenuation - Color::new(0.5, 0.6, 0.7)).length(), 0.0, epsilon = 1e-5);
+    }
+
+    // a rough white surface never gains energy, and under grazing light it scatters more
+    // back towards the light than away from it
+    let rough = OrenNayar::new(white, 1.0);
+    let ray = Ray::new(Point::new(-3.0, 0.0, 1.0), Point::new(3.0, 0.0, -1.0));
+    let record = record(&ray);
+
+    let n = 20000;
+    let mut total = 0.0;
+    let mut back = 0.0;
+    let mut forward = 0.0;
+
+    for _ in 0..n {
+        let (ray_out, attenuation, _) = rough.scatter(&ray, &record).expect("Diffuse always scatters.");
+        total += attenuation.x();
+        if ray_out.direction().x() < 0.0 { back += attenuation.x() } else { forward += attenuation.x() }
+    }
+
+    assert!(total / (n as f32) <= 1.0);
+    assert!(back > 1.2*forward);
+
+    // seen from straight above s vanishes and the factor is A = 1/(1 + (1/2 - 2/(3pi))*sigma)
+    let Material::OrenNayar(rough) = rough else { panic!("Expected an Oren-Nayar material.") };
+    let wi = Point::unit_vector(&Point::new(1.0, 0.0, 1.0));
+    assert_relative_eq!(rough.factor(normal, normal, wi), 1.0 / (1.0 + 0.5 - 2.0/(3.0*PI)), epsilon = 1e-6);
+    assert_relative_eq!(rough.factor(normal, normal, wi), 0.776, epsilon = 1e-3);
+
+}
+
+#[test]
+fn test_eval(){
+
+    // without perfectly specular lobes, the average sampling weight is the integral of
+    // the evaluated bsdf over all directions, here by uniform sampling of the sphere
+    let normal = Point::new(0.0, 0.0, 1.0);
+    let color = Color::new(0.9, 0.6, 0.3);
+    let rough = Texture::Solid(Color::new(0.6, 0.6, 0.6));
+
+    let materials = [
+        Lambertian::new(color),
+        OrenNayar::new(color, 0.8),
+        Metal::new(color, 0.6),
+        Metal::textured(Fresnel::Schlick(color), rough.clone(), 0.6),
+        RoughDielectric::new(1.5, 0.6),
+        Principled { base_color: Texture::Solid(color), roughness: 0.6, sheen: 1.0, clearcoat: 1.0, clearcoat_gloss: 0.5, ..Default::default() }.into(),
+        Principled { base_color: Texture::Solid(color), metallic: 0.5, roughness: 0.7, transmission: 0.5, subsurface: 0.5, ..Default::default() }.into(),
+        Layered::tinted(Lambertian::new(color), 1.5, 0.6, Color::new(0.9, 0.8, 0.7)),
+        Mix::new(Lambertian::new(color), Metal::new(color, 0.6), 0.3),
+    ];
+
+    let direction = Point::new(1.0, 0.3, -1.0);
+    let ray = Ray::new(Point::default() - direction, direction);
+    let record = HitRecord::new(Point::default(), normal, 1.0, &ray,
+        0.5, 0.5, Point::new(1.0, 0.0, 0.0), Point::new(0.0, 1.0, 0.0));
+
+    for (i, material) in materials.into_iter().enumerate() {
+
+        let n = 100000;
+        let sampled: Color = (0..n).filter_map(|_| material.scatter(&ray, &record)).map(|(_, attenuation, _)| attenuation).sum::<Color>() / (n as f32);
+        let evaluated: Color = (0..n).map(|_| material.eval(&ray, &record, Point::random_on_sphere())).sum::<Color>() * (4.0*PI / n as f32);
+
+        for (a, b) in [(sampled.x(), evaluated.x()), (sampled.y(), evaluated.y()), (sampled.z(), evaluated.z())] {
+            assert!((a - b).abs() < 0.03 + 0.03*a, "material {} samples {} but evaluates to {}", i, a, b);
+        }
+
+    }
+
+    // the same direction always evaluates to the same value
+    let light = Point::new(1.0, 0.3, 1.0);
+    let principled: Material = Principled { base_color: Texture::Solid(color), roughness: 0.6, ..Default::default() }.into();
+    assert_eq!(principled.eval(&ray, &record, light), principled.eval(&ray, &record, light));
+
+    // smooth surfaces are left to the scattered rays
+    assert_eq!(Metal::new(color, 0.0).eval(&ray, &record, light), Color::default());
+    assert_eq!(Dielectric::new(1.5).eval(&ray, &record, light), Color::default());
+
+    // and nothing reflects light coming from below the surface
+    assert_eq!(Lambertian::new(color).eval(&ray, &record, -light), Color::default());
+
+}
+
+#[test]
+fn test_wrapped_emission(){
+
+    use crate::texture::Checker;
+
+    let light = DiffuseLight::new(Color::new(4.0, 2.0, 1.0));
+
+    // wrappers keep the emitter, weighted by how often it is hit or picked
+    assert_eq!(Mapped::new(light.clone(), NormalMap::Tangent(Texture::Solid(Color::new(0.5, 0.5, 1.0)))).emission(),
+        Some(Color::new(4.0, 2.0, 1.0)));
+    assert_eq!(Cutout::new(light.clone(), 0.5).emission(), Some(Color::new(2.0, 1.0, 0.5)));
+    assert_eq!(Mix::new(Lambertian::new(Color::new(0.5, 0.5, 0.5)), light.clone(), 0.25).emission(), Some(Color::new(1.0, 0.5, 0.25)));
+    assert_eq!(Mix::new(Lambertian::new(Color::new(0.5, 0.5, 0.5)), Metal::new(Color::new(0.5, 0.5, 0.5), 0.1), 0.25).emission(), None);
+
+    // a varying mask is sampled with its mean
+    let mask = Checker::new(4.0, Color::new(0.0, 0.0, 0.0), Color::new(1.0, 1.0, 1.0));
+    assert_eq!(Cutout::textured(light.clone(), mask.clone()).emission(), Some(Color::new(2.0, 1.0, 0.5)));
+    assert_eq!(Mix::textured(Lambertian::new(Color::new(0.5, 0.5, 0.5)), light.clone(), mask).emission(), Some(Color::new(2.0, 1.0, 0.5)));
+
+}
@@ -0,0 +1,491 @@
+use crate::onb::Onb;
+use crate::ray::Ray;
+use crate::vec3::{Color, Point};
+
+use std::f32::consts::PI;
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::sync::Arc;
+
+//
+// main trait
+pub trait Sample {
+
+    // free flight along the ray, up to t_max in units of the ray parameter, with
+    // the distance sampled on the given color channel
+    fn sample(&self, ray: &Ray, t_max: f32, channel: usize) -> Interaction;
+
+    // fraction of the light that goes through the medium along the ray up to t_max, per channel
+    fn transmittance(&self, ray: &Ray, t_max: f32) -> Color;
+
+}
+
+// outcome of a free flight, with the path contribution and the pdf of every channel, so that
+// walks sampled on one channel can be weighted by the pdf averaged over all of them (spectral MIS)
+#[derive(Debug, Clone)]
+pub enum Interaction {
+    // scattered inside the medium into a new ray, the last color is the radiance emitted
+    // at the event, to be weighted by the path throughput before it
+    Scattered(Ray, Color, Color, Color),
+    // reached t_max without any event
+    Passed(Color, Color),
+}
+
+#[derive(Debug, Clone)]
+pub enum Medium {
+    Homogeneous(Homogeneous),
+    Grid(Grid),
+}
+
+impl Sample for Medium {
+
+    fn sample(&self, ray: &Ray, t_max: f32, channel: usize) -> Interaction {
+        match self {
+            Self::Homogeneous(h) => h.sample(ray, t_max, channel),
+            Self::Grid(g) => g.sample(ray, t_max),
+            // Handle other media here
+        }
+    }
+
+    fn transmittance(&self, ray: &Ray, t_max: f32) -> Color {
+        match self {
+            Self::Homogeneous(h) => h.transmittance(ray, t_max),
+            Self::Grid(g) => g.transmittance(ray, t_max),
+        }
+    }
+
+}
+
+impl Medium {
+
+    // angular distribution of the scattered light, for the lights sampled at the events
+    pub fn phase(&self) -> Phase {
+        match self {
+            Self::Homogeneous(h) => h.phase,
+            Self::Grid(g) => g.phase,
+        }
+    }
+
+}
+
+//
+// phase functions
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Phase {
+    Isotropic,
+    // asymmetry g in ]-1:1[, forward scattering for g > 0
+    HenyeyGreenstein(f32),
+}
+
+impl Phase {
+
+    // phase function value for the angle between the propagation directions, per steradian
+    pub fn evaluate(&self, cos_theta: f32) -> f32 {
+        match self {
+            Self::Isotropic => 1.0 / (4.0*PI),
+            Self::HenyeyGreenstein(g) => {
+                let denominator = 1.0 + g*g - 2.0*g*cos_theta;
+                (1.0 - g*g) / (4.0*PI*denominator*denominator.max(1e-12).sqrt())
+            }
+        }
+    }
+
+    // samples the scattered direction for a ray travelling along direction (unit),
+    // exactly proportional to the phase function so the weight is one
+    pub fn sample(&self, direction: Point) -> Point {
+        match self {
+            Self::Isotropic => Point::random_on_sphere(),
+            Self::HenyeyGreenstein(g) => {
+
+                let g = g.clamp(-0.999, 0.999);
+                let xi = Point::random_float();
+
+                // inverted cdf of the cosine to the propagation direction
+                let cos_theta = if g.abs() < 1e-3 {
+                    1.0 - 2.0*xi
+                } else {
+                    let s = (1.0 - g*g) / (1.0 - g + 2.0*g*xi);
+                    ((1.0 + g*g - s*s) / (2.0*g)).clamp(-1.0, 1.0)
+                };
+                let sin_theta = (1.0 - cos_theta*cos_theta).max(0.0).sqrt();
+                let phi = 2.0*PI*Point::random_float();
+
+                Onb::new(direction).to_world(Point::new(sin_theta*phi.cos(), sin_theta*phi.sin(), cos_theta))
+
+            }
+        }
+    }
+
+}
+
+//
+// Homogeneous medium
+#[derive(Debug, Clone)]
+pub struct Homogeneous {
+
+    // extinction coefficient per unit length
+    sigma_t: Color,
+    // single scattering albedo, the scattered fraction of the extinction
+    albedo: Color,
+    phase: Phase,
+
+}
+
+impl Homogeneous {
+
+    pub fn new(sigma_t: Color, albedo: Color, phase: Phase) -> Medium {
+        Medium::Homogeneous(Self { sigma_t, albedo, phase })
+    }
+
+    fn sample(&self, ray: &Ray, t_max: f32, channel: usize) -> Interaction {
+
+        let sigma_t = ray.spectrum(self.sigma_t);
+        let albedo = ray.spectrum(self.albedo);
+
+        let length = ray.direction().length();
+        let distance = t_max*length;
+
+        let sigma = [sigma_t.x(), sigma_t.y(), sigma_t.z()][channel.min(2)];
+        let s = if sigma > 0.0 { -(1.0 - Point::random_float()).ln() / sigma } else { f32::INFINITY };
+
+        if s < distance {
+
+            let transmittance = (-sigma_t*s).exp();
+
+            let direction = self.phase.sample(ray.direction() / length);
+            let ray_out = Ray::new(ray.at(s / length), direction).set_wavelengths(ray.wavelengths());
+
+            return Interaction::Scattered(ray_out, transmittance*sigma_t*albedo, transmittance*sigma_t, Color::default());
+
+        }
+
+        let transmittance = (-sigma_t*distance).exp();
+
+        return Interaction::Passed(transmittance, transmittance);
+
+    }
+
+    fn transmittance(&self, ray: &Ray, t_max: f32) -> Color {
+        let distance = t_max*ray.direction().length();
+        (-ray.spectrum(self.sigma_t)*distance).exp()
+    }
+
+}
+
+//
+// Grid (heterogeneous medium from a dense voxel grid)
+#[derive(Debug, Clone)]
+pub struct Grid {
+
+    // voxel counts along x, y and z, the values are stored x fastest
+    resolution: [usize; 3],
+    density: Arc<Vec<f32>>,
+    // scalar emission grid for fire, scaled by the emission color
+    emission: Option<Arc<Vec<f32>>>,
+    emission_color: Color,
+
+    // world space bounds of the grid
+    min: Point,
+    max: Point,
+
+    // extinction per unit length at density one
+    sigma_t: f32,
+    albedo: Color,
+    phase: Phase,
+
+    // upper bound of the extinction, for delta and ratio tracking
+    majorant: f32,
+
+}
+
+impl Grid {
+
+    pub fn new(resolution: [usize; 3], density: Vec<f32>, min: Point, max: Point, sigma_t: f32, albedo: Color, phase: Phase) -> Self {
+
+        assert_eq!(density.len(), resolution[0]*resolution[1]*resolution[2], "Grid size does not match the voxel count.");
+
+        let majorant = sigma_t * density.iter().cloned().fold(0.0, f32::max);
+
+        Self { resolution, density: Arc::new(density), emission: None, emission_color: Color::default(), min, max, sigma_t, albedo, phase, majorant }
+
+    }
+
+    // emission values on the same grid as the density, e.g. a temperature mapped to [0:1]
+    pub fn set_emission(self, emission: Vec<f32>, emission_color: Color) -> Self {
+
+        assert_eq!(emission.len(), self.density.len(), "Emission grid does not match the density grid.");
+
+        Self { emission: Some(Arc::new(emission)), emission_color, ..self }
+
+    }
+
+    // samples a procedural density at the voxel centers
+    pub fn from_fn<F: Fn(Point) -> f32>(resolution: [usize; 3], min: Point, max: Point, f: F) -> Vec<f32> {
+
+        let size = max - min;
+        let mut values = Vec::with_capacity(resolution[0]*resolution[1]*resolution[2]);
+
+        for k in 0..resolution[2] {
+            for j in 0..resolution[1] {
+                for i in 0..resolution[0] {
+                    let local = Point::new(
+                        (i as f32 + 0.5) / resolution[0] as f32,
+                        (j as f32 + 0.5) / resolution[1] as f32,
+                        (k as f32 + 0.5) / resolution[2] as f32,
+                    );
+                    values.push(f(min + size*local));
+                }
+            }
+        }
+
+        return values;
+
+    }
+
+    pub fn load_raw<P: AsRef<Path>>(path: P, resolution: [usize; 3]) -> io::Result<Vec<f32>> {
+
+        let bytes = fs::read(path)?;
+
+        return Grid::parse_raw(&bytes, resolution);
+
+    }
+
+    // headerless little endian 32-bit floats, x fastest
+    pub fn parse_raw(bytes: &[u8], resolution: [usize; 3]) -> io::Result<Vec<f32>> {
+
+        let size = resolution.iter().try_fold(4usize, |size, &n| size.checked_mul(n));
+
+        if size != Some(bytes.len()) {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, "Raw grid size does not match the resolution."));
+        }
+
+        let values = bytes.chunks_exact(4).map(|b| f32::from_le_bytes([b[0], b[1], b[2], b[3]])).collect();
+
+        return Ok(values);
+
+    }
+
+    pub fn bounds(&self) -> (Point, Point) {(self.min, self.max)}
+
+    // trilinear interpolation of the voxel values, zero outside of the grid
+    fn lookup(&self, values: &[f32], p: Point) -> f32 {
+
+        let size = self.max - self.min;
+        let local = (p - self.min) / size;
+
+        if [local.x(), local.y(), local.z()].iter().any(|c| !(0.0..=1.0).contains(c)) { return 0.0 }
+
+        let [nx, ny, nz] = self.resolution;
+        let coordinate = |c: f32, n: usize| {
+            let x = (c*n as f32 - 0.5).clamp(0.0, (n - 1) as f32);
+            let i = (x.floor() as usize).min(n.saturating_sub(2));
+            (i, (i + 1).min(n - 1), x - i as f32)
+        };
+
+        let (i0, i1, fx) = coordinate(local.x(), nx);
+        let (j0, j1, fy) = coordinate(local.y(), ny);
+        let (k0, k1, fz) = coordinate(local.z(), nz);
+
+        let value = |i: usize, j: usize, k: usize| values[(k*ny + j)*nx + i];
+        let lerp = |a: f32, b: f32, t: f32| a + (b - a)*t;
+
+        let c00 = lerp(value(i0, j0, k0), value(i1, j0, k0), fx);
+        let c10 = lerp(value(i0, j1, k0), value(i1, j1, k0), fx);
+        let c01 = lerp(value(i0, j0, k1), value(i1, j0, k1), fx);
+        let c11 = lerp(value(i0, j1, k1), value(i1, j1, k1), fx);
+
+        return lerp(lerp(c00, c10, fy), lerp(c01, c11, fy), fz);
+
+    }
+
+    fn sigma_t(&self, p: Point) -> f32 {
+        self.sigma_t * self.lookup(&self.density, p)
+    }
+
+    // delta tracking: tentative collisions at the majorant rate, accepted with probability
+    // sigma_t / majorant, the extinction being grey the weights need no spectral MIS
+    fn sample(&self, ray: &Ray, t_max: f32) -> Interaction {
+
+        let white = Color::new(1.0, 1.0, 1.0);
+
+        if self.majorant <= 0.0 { return Interaction::Passed(white, white) }
+
+        let length = ray.direction().length();
+        let direction = ray.direction() / length;
+        let distance = t_max*length;
+
+        let mut s = 0.0;
+
+        loop {
+
+            s -= (1.0 - Point::random_float()).ln() / self.majorant;
+
+            if s >= distance { return Interaction::Passed(white, white) }
+
+            let p = ray.origin() + direction*s;
+
+            if self.sigma_t(p) / self.majorant > Point::random_float() {
+
+                // collision estimator of the emission, the absorbed fraction emits
+                let albedo = ray.spectrum(self.albedo);
+                let emission = match &self.emission {
+                    Some(values) => ray.spectrum(self.emission_color*self.lookup(values, p)) * (-albedo + 1.0),
+                    None => Color::default(),
+                };
+
+                let ray_out = Ray::new(p, self.phase.sample(direction)).set_wavelengths(ray.wavelengths());
+
+                return Interaction::Scattered(ray_out, albedo, white, emission);
+
+            }
+
+        }
+
+    }
+
+    // ratio tracking: the product of the null collision probabilities is an unbiased transmittance
+    fn transmittance(&self, ray: &Ray, t_max: f32) -> Color {
+
+        if self.majorant <= 0.0 { return Color::new(1.0, 1.0, 1.0) }
+
+        let length = ray.direction().length();
+        let direction = ray.direction() / length;
+        let distance = t_max*length;
+
+        let mut s = 0.0;
+        let mut transmittance = 1.0;
+
+        loop {
+
+            s -= (1.0 - Point::random_float()).ln() / self.majorant;
+
+            if s >= distance { break }
+
+            transmittance *= 1.0 - self.sigma_t(ray.origin() + direction*s) / self.majorant;
+
+            if transmittance <= 0.0 { break }
+
+        }
+
+        return Color::new(transmittance, transmittance, transmittance);
+
+    }
+
+}
+
+//
+// tests
+#[test]
+fn test_homogeneous(){
+
+    let ray = Ray::new(Point::default(), Point::new(0.0, 0.0, 2.0));
+    let n = 50000;
+    let average = |c: Color| (c.x() + c.y() + c.z()) / 3.0;
+
+    // a grey non absorbing medium only changes the direction, scattering before t_max with
+    // the probability 1 - exp(-sigma*d)
+    let medium = Homogeneous::new(Color::new(0.5, 0.5, 0.5), Color::new(1.0, 1.0, 1.0), Phase::Isotropic);
+
+    let mut scattered = 0;
+    for i in 0..n {
+        match medium.sample(&ray, 1.0, i % 3) {
+            Interaction::Scattered(ray_out, value, pdf, _) => {
+                scattered += 1;
+                assert!(ray_out.origin().z() < 2.0);
+                assert_relative_eq!(value.x() / average(pdf), 1.0, epsilon = 1e-4);
+            }
+            Interaction::Passed(value, pdf) => assert_relative_eq!(value.x() / average(pdf), 1.0, epsilon = 1e-4),
+        }
+    }
+    assert_relative_eq!(scattered as f32 / n as f32, 1.0 - (-1.0_f32).exp(), epsilon = 0.01);
+
+    // with chromatic extinction every channel still sees its own transmittance
+    let sigma_t = Color::new(0.1, 0.5, 2.0);
+    let medium = Homogeneous::new(sigma_t, Color::new(0.8, 0.8, 0.8), Phase::Isotropic);
+
+    let transmitted: Color = (0..n).filter_map(|i| match medium.sample(&ray, 1.0, i % 3) {
+        Interaction::Passed(value, pdf) => Some(value / average(pdf)),
+        Interaction::Scattered(..) => None,
+    }).sum();
+    let transmitted = transmitted / (n as f32);
+    let expected = (-sigma_t*2.0).exp();
+
+    assert_relative_eq!((transmitted - expected).length(), 0.0, epsilon = 0.02);
+
+}
+
+#[test]
+fn test_phase(){
+
+    // both phase functions integrate to one over the sphere
+    for phase in [Phase::Isotropic, Phase::HenyeyGreenstein(0.7), Phase::HenyeyGreenstein(-0.3)] {
+        let n = 2000;
+        let integral: f32 = (0..n).map(|i| {
+            let cos_theta = -1.0 + 2.0*(i as f32 + 0.5) / (n as f32);
+            phase.evaluate(cos_theta) * 2.0*PI * 2.0 / (n as f32)
+        }).sum();
+        assert_relative_eq!(integral, 1.0, epsilon = 1e-3);
+    }
+
+    // the sampled mean cosine is the asymmetry
+    let direction = Point::unit_vector(&Point::new(1.0, 2.0, -0.5));
+    for g in [0.0, 0.6, -0.4] {
+        let n = 50000;
+        let mean: f32 = (0..n).map(|_| Phase::HenyeyGreenstein(g).sample(direction).dot(direction)).sum::<f32>() / (n as f32);
+        assert_relative_eq!(mean, g, epsilon = 0.02);
+    }
+
+}
+
+#[test]
+fn test_grid(){
+
+    let min = Point::new(-1.0, -1.0, -1.0);
+    let max = Point::new(1.0, 1.0, 1.0);
+
+    // a constant grid behaves like the homogeneous medium, optical depth 0.5*2*2
+    let resolution = [4, 4, 4];
+    let density = Grid::from_fn(resolution, min, max, |_| 0.5);
+    let grid = Grid::new(resolution, density, min, max, 2.0, Color::new(1.0, 1.0, 1.0), Phase::Isotropic);
+
+    let ray = Ray::new(Point::new(0.0, 0.0, -1.0), Point::new(0.0, 0.0, 1.0));
+    let expected = (-2.0_f32).exp();
+
+    let n = 20000;
+    let passed = (0..n).filter(|_| matches!(grid.sample(&ray, 2.0), Interaction::Passed(..))).count();
+    assert_relative_eq!(passed as f32 / n as f32, expected, epsilon = 0.01);
+
+    let transmittance: f32 = (0..n).map(|_| grid.transmittance(&ray, 2.0).x()).sum::<f32>() / (n as f32);
+    assert_relative_eq!(transmittance, expected, epsilon = 0.01);
+
+    // a linear ramp along x is interpolated, and the grid is empty outside of its bounds
+    let density = Grid::from_fn([8, 2, 2], min, max, |p| p.x() + 1.0);
+    let grid = Grid::new([8, 2, 2], density, min, max, 1.0, Color::new(1.0, 1.0, 1.0), Phase::Isotropic);
+    assert_relative_eq!(grid.sigma_t(Point::new(0.0, 0.3, -0.2)), 1.0, epsilon = 1e-5);
+    assert_relative_eq!(grid.sigma_t(Point::new(0.25, 0.0, 0.0)), 1.25, epsilon = 1e-5);
+    assert_eq!(grid.sigma_t(Point::new(1.5, 0.0, 0.0)), 0.0);
+
+    // through a ramp the ratio tracking estimate matches the optical depth
+    let ray = Ray::new(Point::new(-1.0, 0.0, 0.0), Point::new(1.0, 0.0, 0.0));
+    let transmittance: f32 = (0..n).map(|_| grid.transmittance(&ray, 2.0).x()).sum::<f32>() / (n as f32);
+    assert_relative_eq!(transmittance, (-2.0_f32).exp(), epsilon = 0.01);
+
+    // an absorbing emissive grid emits at collisions
+    let grid = Grid::new([1, 1, 1], vec![1.0], min, max, 1.0, Color::new(0.0, 0.0, 0.0), Phase::Isotropic)
+        .set_emission(vec![1.0], Color::new(2.0, 1.0, 0.5));
+    let ray = Ray::new(Point::new(0.0, 0.0, -1.0), Point::new(0.0, 0.0, 1.0));
+    loop {
+        if let Interaction::Scattered(_, value, _, emission) = grid.sample(&ray, 2.0) {
+            assert_eq!(value, Color::new(0.0, 0.0, 0.0));
+            assert_eq!(emission, Color::new(2.0, 1.0, 0.5));
+            break;
+        }
+    }
+
+    let bytes: Vec<u8> = [0.5_f32, 1.5].iter().flat_map(|v| v.to_le_bytes()).collect();
+    assert_eq!(Grid::parse_raw(&bytes, [2, 1, 1]).expect("Valid grid."), vec![0.5, 1.5]);
+    assert!(Grid::parse_raw(&bytes, [3, 1, 1]).is_err());
+    assert!(Grid::parse_raw(&bytes, [usize::MAX / 2, 2, 1]).is_err());
+
+}
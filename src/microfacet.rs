@@ -0,0 +1,437 @@
+use std::f32::consts::PI;
+use std::sync::OnceLock;
+
+use crate::vec3::{Color, Point};
+
+// below this the distribution is treated as a perfectly smooth (delta) surface
+pub const MIN_ALPHA: f32 = 1e-3;
+
+// resolution of the directional albedo table, over alpha and cos(theta)
+const ALBEDO_SIZE: usize = 32;
+const ALBEDO_SAMPLES: usize = 16;
+const MAX_TABLE_ALPHA: f32 = 2.0;
+
+//
+// GGX / Trowbridge-Reitz distribution, all directions in the local shading frame (normal = +z)
+#[derive(Debug, Clone, Copy)]
+pub struct Ggx {
+    alpha_x: f32,
+    alpha_y: f32,
+}
+
+impl Ggx {
+
+    pub fn new(alpha_x: f32, alpha_y: f32) -> Self {
+        Self { alpha_x: alpha_x.max(MIN_ALPHA), alpha_y: alpha_y.max(MIN_ALPHA) }
+    }
+
+    pub fn from_roughness(roughness: f32, anisotropy: f32) -> Self {
+
+        // perceptual roughness is squared, anisotropy in [0:1] stretches along the tangent
+        let alpha = roughness.clamp(0.0, 1.0).powi(2);
+        let aspect = (1.0 - 0.9*anisotropy.clamp(0.0, 1.0)).sqrt();
+
+        Ggx::new(alpha / aspect, alpha * aspect)
+
+    }
+
+    pub fn is_smooth(&self) -> bool {
+        self.alpha_x.max(self.alpha_y) <= MIN_ALPHA
+    }
+
+    pub fn d(&self, h: Point) -> f32 {
+
+        if h.z() <= 0.0 { return 0.0 }
+
+        let x = h.x() / self.alpha_x;
+        let y = h.y() / self.alpha_y;
+        let e = x*x + y*y + h.z()*h.z();
+
+        return 1.0 / (PI * self.alpha_x * self.alpha_y * e * e);
+
+    }
+
+    pub fn lambda(&self, w: Point) -> f32 {
+
+        let cos2 = w.z()*w.z();
+
+        if cos2 == 0.0 { return f32::INFINITY }
+
+        let x = self.alpha_x*w.x();
+        let y = self.alpha_y*w.y();
+        let tan2 = (x*x + y*y) / cos2;
+
+        return 0.5*((1.0 + tan2).sqrt() - 1.0);
+
+    }
+
+    pub fn g1(&self, w: Point) -> f32 {
+        1.0 / (1.0 + self.lambda(w))
+    }
+
+    // height-correlated masking-shadowing
+    pub fn g2(&self, wo: Point, wi: Point) -> f32 {
+        1.0 / (1.0 + self.lambda(wo) + self.lambda(wi))
+    }
+
+    // distribution of visible normals seen from wo
+    pub fn visible_d(&self, wo: Point, h: Point) -> f32 {
+
+        if wo.z() <= 0.0 { return 0.0 }
+
+        return self.g1(wo) * wo.dot(h).max(0.0) * self.d(h) / wo.z();
+
+    }
+
+    pub fn sample_visible(&self, wo: Point, u1: f32, u2: f32) -> Point {
+
+        // Heitz 2018, "Sampling the GGX Distribution of Visible Normals"
+        let vh = Point::unit_vector(&Point::new(self.alpha_x*wo.x(), self.alpha_y*wo.y(), wo.z()));
+
+        let length_square = vh.x()*vh.x() + vh.y()*vh.y();
+        let t1 = if length_square > 0.0 {
+            Point::new(-vh.y(), vh.x(), 0.0) / length_square.sqrt()
+        } else {
+            Point::new(1.0, 0.0, 0.0)
+        };
+        let t2 = vh.cross(t1);
+
+        let r = u1.sqrt();
+        let phi = 2.0*PI*u2;
+        let p1 = r*phi.cos();
+        let p2 = r*phi.sin();
+        let s = 0.5*(1.0 + vh.z());
+        let p2 = (1.0 - s)*(1.0 - p1*p1).max(0.0).sqrt() + s*p2;
+
+        let nh = t1*p1 + t2*p2 + vh*(1.0 - p1*p1 - p2*p2).max(0.0).sqrt();
+
+        return Point::unit_vector(&Point::new(self.alpha_x*nh.x(), self.alpha_y*nh.y(), nh.z().max(1e-6)));
+
+    }
+
+    // fraction of energy a white single scattering surface reflects towards wo
+    pub fn directional_albedo(&self, wo: Point) -> f32 {
+
+        let table = ALBEDO_TABLE.get_or_init(Ggx::albedo_table);
+
+        // the table is isotropic, anisotropic distributions use the mean alpha as an approximation
+        let alpha = (self.alpha_x*self.alpha_y).sqrt();
+
+        // bilinear lookup
+        let x = (alpha / MAX_TABLE_ALPHA).clamp(0.0, 1.0) * (ALBEDO_SIZE - 1) as f32;
+        let y = wo.z().clamp(0.0, 1.0) * (ALBEDO_SIZE - 1) as f32;
+
+        let (i, j) = ((x as usize).min(ALBEDO_SIZE - 2), (y as usize).min(ALBEDO_SIZE - 2));
+        let (fx, fy) = (x - i as f32, y - j as f32);
+
+        let at = |i: usize, j: usize| table[i*ALBEDO_SIZE + j];
+        let low = at(i, j)*(1.0 - fy) + at(i, j + 1)*fy;
+        let high = at(i + 1, j)*(1.0 - fy) + at(i + 1, j + 1)*fy;
+
+        return low*(1.0 - fx) + high*fx;
+
+    }
+
+    // scale applied to single scattering to restore the energy lost to masking (Turquin 2019),
+    // f0 is the Fresnel reflectance at normal incidence
+    pub fn multiple_scattering(&self, wo: Point, f0: Color) -> Color {
+
+        let albedo = self.directional_albedo(wo).max(1e-3);
+
+        return f0*(1.0/albedo - 1.0) + 1.0;
+
+    }
+
+    fn albedo_table() -> Vec<f32> {
+
+        let mut table = vec![0.0; ALBEDO_SIZE*ALBEDO_SIZE];
+
+        for i in 0..ALBEDO_SIZE {
+            for j in 0..ALBEDO_SIZE {
+
+                let alpha = (MAX_TABLE_ALPHA * i as f32 / (ALBEDO_SIZE - 1) as f32).max(MIN_ALPHA);
+                let cos_o = (j as f32 / (ALBEDO_SIZE - 1) as f32).max(1e-3);
+                let wo = Point::new((1.0 - cos_o*cos_o).sqrt(), 0.0, cos_o);
+                let ggx = Ggx::new(alpha, alpha);
+
+                // stratified visible normal sampling, the estimator is G2/G1
+                let mut sum = 0.0;
+
+                for a in 0..ALBEDO_SAMPLES {
+                    for b in 0..ALBEDO_SAMPLES {
+
+                        let u1 = (a as f32 + 0.5) / ALBEDO_SAMPLES as f32;
+                        let u2 = (b as f32 + 0.5) / ALBEDO_SAMPLES as f32;
+                        let h = ggx.sample_visible(wo, u1, u2);
+                        let wi = Point::reflect(-wo, h);
+
+                        if wi.z() > 0.0 { sum += ggx.g2(wo, wi) / ggx.g1(wo) }
+
+                    }
+                }
+
+                table[i*ALBEDO_SIZE + j] = sum / (ALBEDO_SAMPLES*ALBEDO_SAMPLES) as f32;
+
+            }
+        }
+
+        return table;
+
+    }
+
+}
+
+static ALBEDO_TABLE: OnceLock<Vec<f32>> = OnceLock::new();
+
+//
+// Fresnel terms
+
+// unpolarized reflectance of a dielectric interface, eta = n_transmitted / n_incident
+pub fn fresnel_dielectric(cos_i: f32, eta: f32) -> f32 {
+
+    let cos_i = cos_i.clamp(0.0, 1.0);
+    let sin2_t = (1.0 - cos_i*cos_i) / (eta*eta);
+
+    // total internal reflection
+    if sin2_t >= 1.0 { return 1.0 }
+
+    let cos_t = (1.0 - sin2_t).sqrt();
+
+    let r_parallel = (eta*cos_i - cos_t) / (eta*cos_i + cos_t);
+    let r_perpendicular = (cos_i - eta*cos_t) / (cos_i + eta*cos_t);
+
+    return 0.5*(r_parallel*r_parallel + r_perpendicular*r_perpendicular);
+
+}
+
+// unpolarized reflectance of a conductor with complex index of refraction eta + i*k
+pub fn fresnel_conductor(cos_i: f32, eta: Color, k: Color) -> Color {
+
+    let channel = |eta: f32, k: f32| {
+
+        let cos2 = cos_i.clamp(0.0, 1.0).powi(2);
+        let sin2 = 1.0 - cos2;
+        let eta2 = eta*eta;
+        let k2 = k*k;
+
+        let t0 = eta2 - k2 - sin2;
+        let a2_plus_b2 = (t0*t0 + 4.0*eta2*k2).sqrt();
+        let t1 = a2_plus_b2 + cos2;
+        let a = (0.5*(a2_plus_b2 + t0)).max(0.0).sqrt();
+        let t2 = 2.0*cos_i*a;
+        let r_s = (t1 - t2) / (t1 + t2);
+
+        let t3 = cos2*a2_plus_b2 + sin2*sin2;
+        let t4 = t2*sin2;
+        let r_p = r_s*(t3 - t4) / (t3 + t4);
+
+        0.5*(r_p + r_s)
+
+    };
+
+    return Color::new(channel(eta.x(), k.x()), channel(eta.y(), k.y()), channel(eta.z(), k.z()));
+
+}
+
+pub fn fresnel_schlick(cos_i: f32, f0: Color) -> Color {
+
+    let m = (1.0 - cos_i.clamp(0.0, 1.0)).powi(5);
+
+    return f0 + (-f0 + 1.0)*m;
+
+}
+
+// unpolarized reflectance of a thin film of real index film_eta and the given thickness (nm)
+// between an outer medium of real index eta_i and a substrate of complex index eta + i*k,
+// summing the multiple reflections inside the film (Airy) at one wavelength (nm)
+pub fn fresnel_thin_film(cos_i: f32, eta_i: f32, film_eta: f32, thickness: f32, eta: f32, k: f32, lambda: f32) -> f32 {
+
+    let cos_i = cos_i.clamp(0.0, 1.0);
+
+    // n*sin(theta) is the same in every layer, n*cos(theta) follows and turns complex past the critical angle
+    let sin2 = eta_i*eta_i*(1.0 - cos_i*cos_i);
+    let n1 = Complex::real(eta_i);
+    let n2 = Complex::real(film_eta);
+    let n3 = Complex::new(eta, k);
+    let n1_cos = Complex::real(eta_i*cos_i);
+    let n2_cos = (n2*n2 - Complex::real(sin2)).sqrt();
+    let n3_cos = (n3*n3 - Complex::real(sin2)).sqrt();
+
+    // amplitude coefficients of an interface, written without dividing by the indices
+    let r_s = |a_cos: Complex, b_cos: Complex| (a_cos - b_cos) / (a_cos + b_cos);
+    let r_p = |a: Complex, a_cos: Complex, b: Complex, b_cos: Complex| {
+        let (x, y) = (b*b*a_cos, a*a*b_cos);
+        (x - y) / (x + y)
+    };
+
+    // phase difference of one round trip through the film
+    let delta = n2_cos * Complex::real(4.0*PI*thickness / lambda);
+    // exp(i*delta), decaying when the wave is evanescent in the film
+    let phase = Complex::new(delta.re.cos(), delta.re.sin()) * Complex::real((-delta.im).exp());
+
+    let airy = |r12: Complex, r23: Complex| {
+        let r = (r12 + r23*phase) / (Complex::real(1.0) + r12*r23*phase);
+        r.norm_square()
+    };
+
+    let reflectance_s = airy(r_s(n1_cos, n2_cos), r_s(n2_cos, n3_cos));
+    let reflectance_p = airy(r_p(n1, n1_cos, n2, n2_cos), r_p(n2, n2_cos, n3, n3_cos));
+
+    return (0.5*(reflectance_s + reflectance_p)).clamp(0.0, 1.0);
+
+}
+
+// just enough complex arithmetic for the thin film amplitudes
+#[derive(Debug, Clone, Copy)]
+struct Complex {
+    re: f32,
+    im: f32,
+}
+
+impl Complex {
+
+    fn new(re: f32, im: f32) -> Self {
+        Self { re, im }
+    }
+
+    fn real(re: f32) -> Self {
+        Self { re, im: 0.0 }
+    }
+
+    fn norm_square(&self) -> f32 {
+        self.re*self.re + self.im*self.im
+    }
+
+    // principal square root, with a non negative real part
+    fn sqrt(&self) -> Self {
+        let norm = self.norm_square().sqrt();
+        let re = (0.5*(norm + self.re)).max(0.0).sqrt();
+        let im = (0.5*(norm - self.re)).max(0.0).sqrt();
+        Self { re, im: if self.im < 0.0 { -im } else { im } }
+    }
+
+}
+
+impl std::ops::Add for Complex {
+    type Output = Self;
+    fn add(self, other: Self) -> Self { Self::new(self.re + other.re, self.im + other.im) }
+}
+
+impl std::ops::Sub for Complex {
+    type Output = Self;
+    fn sub(self, other: Self) -> Self { Self::new(self.re - other.re, self.im - other.im) }
+}
+
+impl std::ops::Mul for Complex {
+    type Output = Self;
+    fn mul(self, other: Self) -> Self {
+        Self::new(self.re*other.re - self.im*other.im, self.re*other.im + self.im*other.re)
+    }
+}
+
+impl std::ops::Div for Complex {
+    type Output = Self;
+    fn div(self, other: Self) -> Self {
+        let norm = other.norm_square();
+        Self::new((self.re*other.re + self.im*other.im) / norm, (self.im*other.re - self.re*other.im) / norm)
+    }
+}
+
+//
+// tests
+#[test]
+fn test_fresnel(){
+
+    // normal incidence on glass
+    assert_relative_eq!(fresnel_dielectric(1.0, 1.5), 0.04, epsilon = 1e-6);
+    assert_relative_eq!(fresnel_dielectric(0.0, 1.5), 1.0, epsilon = 1e-6);
+
+    // total internal reflection from the inside
+    assert_relative_eq!(fresnel_dielectric(0.5, 1.0/1.5), 1.0);
+
+    // a conductor without absorption is a dielectric
+    let r = fresnel_conductor(0.7, Color::new(1.5, 1.5, 1.5), Color::new(0.0, 0.0, 0.0));
+    assert_relative_eq!(r.x(), fresnel_dielectric(0.7, 1.5), epsilon = 1e-5);
+
+    // a strongly absorbing conductor is almost a perfect mirror
+    let r = fresnel_conductor(1.0, Color::new(1.0, 1.0, 1.0), Color::new(100.0, 100.0, 100.0));
+    assert!(r.x() > 0.99);
+
+    assert_eq!(fresnel_schlick(1.0, Color::new(0.2, 0.5, 0.9)), Color::new(0.2, 0.5, 0.9));
+    assert_eq!(fresnel_schlick(0.0, Color::new(0.2, 0.5, 0.9)), Color::new(1.0, 1.0, 1.0));
+
+}
+
+#[test]
+fn test_thin_film(){
+
+    // a film of zero thickness is the bare interface
+    for cos_i in [1.0, 0.7, 0.2] {
+        assert_relative_eq!(fresnel_thin_film(cos_i, 1.0, 1.33, 0.0, 1.5, 0.0, 550.0), fresnel_dielectric(cos_i, 1.5), epsilon = 1e-5);
+        let conductor = fresnel_conductor(cos_i, Color::new(0.2, 0.2, 0.2), Color::new(3.0, 3.0, 3.0));
+        assert_relative_eq!(fresnel_thin_film(cos_i, 1.0, 1.6, 0.0, 0.2, 3.0, 550.0), conductor.x(), epsilon = 1e-4);
+    }
+
+    // a quarter wave coating of index sqrt(n) cancels the reflection at its design wavelength only
+    let film = 1.5_f32.sqrt();
+    let thickness = 550.0 / (4.0*film);
+    assert!(fresnel_thin_film(1.0, 1.0, film, thickness, 1.5, 0.0, 550.0) < 1e-5);
+    assert!(fresnel_thin_film(1.0, 1.0, film, thickness, 1.5, 0.0, 400.0) > 1e-3);
+
+    // a soap film in air oscillates with the wavelength and stays a valid reflectance
+    let reflectances: Vec<f32> = (0..34).map(|i| fresnel_thin_film(0.8, 1.0, 1.33, 500.0, 1.0, 0.0, 380.0 + 10.0*i as f32)).collect();
+    let max = reflectances.iter().cloned().fold(0.0, f32::max);
+    let min = reflectances.iter().cloned().fold(1.0, f32::min);
+    assert!(min < 0.01 && max > 0.05 && max <= 1.0);
+
+    // total internal reflection stays total under the film
+    assert_relative_eq!(fresnel_thin_film(0.3, 1.5, 1.33, 200.0, 1.0, 0.0, 550.0), 1.0, epsilon = 1e-5);
+
+}
+
+#[test]
+fn test_ggx(){
+
+    // the projected area of the microfacets is one: integral of D(h) cos(h) over the hemisphere
+    let ggx = Ggx::new(0.3, 0.6);
+    let n = 400;
+    let mut integral = 0.0;
+
+    for i in 0..n {
+        for j in 0..n {
+
+            // uniform grid over cos(theta) and phi
+            let cos_theta = (i as f32 + 0.5) / (n as f32);
+            let phi = 2.0*PI*(j as f32 + 0.5) / (n as f32);
+            let sin_theta = (1.0 - cos_theta*cos_theta).sqrt();
+            let h = Point::new(sin_theta*phi.cos(), sin_theta*phi.sin(), cos_theta);
+
+            integral += ggx.d(h) * cos_theta * 2.0*PI / ((n*n) as f32);
+
+        }
+    }
+
+    assert_relative_eq!(integral, 1.0, epsilon = 2e-2);
+
+    // visible normals face the viewer
+    let wo = Point::unit_vector(&Point::new(0.6, -0.2, 0.3));
+    for i in 0..100 {
+        let h = ggx.sample_visible(wo, (i as f32 + 0.5) / 100.0, ((i*37) % 100) as f32 / 100.0);
+        assert_relative_eq!(h.length(), 1.0, epsilon = 1e-5);
+        assert!(h.z() > 0.0);
+        assert!(wo.dot(h) >= -1e-6);
+    }
+
+    assert!(Ggx::from_roughness(0.0, 0.0).is_smooth());
+    assert!(!Ggx::from_roughness(0.5, 0.0).is_smooth());
+    assert!(ggx.g2(wo, wo) <= ggx.g1(wo));
+
+    // smooth surfaces keep all energy, rough ones lose some to masking
+    let wo = Point::new(0.6, 0.0, 0.8);
+    assert_relative_eq!(Ggx::new(0.0, 0.0).directional_albedo(wo), 1.0, epsilon = 1e-3);
+    let rough = Ggx::new(1.0, 1.0);
+    assert!(rough.directional_albedo(wo) < 0.5);
+    let white = rough.multiple_scattering(wo, Color::new(1.0, 1.0, 1.0));
+    assert_relative_eq!(white.x()*rough.directional_albedo(wo), 1.0, epsilon = 1e-5);
+
+}
@@ -0,0 +1,219 @@
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
+use rand::{Rng, SeedableRng};
+use rand_distr::StandardNormal;
+
+use crate::vec3::Point;
+
+const POINT_COUNT: usize = 256;
+
+//
+// Perlin noise struct
+#[derive(Debug, Clone)]
+pub struct Perlin {
+
+    gradients: Vec<Point>,
+    perm_x: Vec<usize>,
+    perm_y: Vec<usize>,
+    perm_z: Vec<usize>,
+
+}
+
+impl Perlin {
+
+    pub fn new(seed: u64) -> Self {
+
+        let mut rng = StdRng::seed_from_u64(seed);
+
+        let gradients = (0..POINT_COUNT).map(|_| {
+            let x = rng.sample::<f32,_>(StandardNormal);
+            let y = rng.sample::<f32,_>(StandardNormal);
+            let z = rng.sample::<f32,_>(StandardNormal);
+            Point::unit_vector(&Point::new(x, y, z))
+        }).collect();
+
+        let permutation = |rng: &mut StdRng| {
+            let mut p: Vec<usize> = (0..POINT_COUNT).collect();
+            p.shuffle(rng);
+            p
+        };
+
+        let perm_x = permutation(&mut rng);
+        let perm_y = permutation(&mut rng);
+        let perm_z = permutation(&mut rng);
+
+        Self { gradients, perm_x, perm_y, perm_z }
+
+    }
+
+    fn hash(&self, i: i64, j: i64, k: i64) -> usize {
+        let mask = (POINT_COUNT - 1) as i64;
+        self.perm_x[(i & mask) as usize] ^ self.perm_y[(j & mask) as usize] ^ self.perm_z[(k & mask) as usize]
+    }
+
+    // gradient noise in about [-1:1], zero at the lattice points
+    pub fn noise(&self, p: Point) -> f32 {
+
+        let (fx, fy, fz) = (p.x().floor(), p.y().floor(), p.z().floor());
+        let (u, v, w) = (p.x() - fx, p.y() - fy, p.z() - fz);
+        let (i, j, k) = (fx as i64, fy as i64, fz as i64);
+
+        // quintic fade curve
+        let fade = |t: f32| t*t*t*(t*(t*6.0 - 15.0) + 10.0);
+        let (uu, vv, ww) = (fade(u), fade(v), fade(w));
+
+        let mut accumulated = 0.0;
+
+        for di in 0..2 {
+            for dj in 0..2 {
+                for dk in 0..2 {
+
+                    let gradient = self.gradients[self.hash(i + di, j + dj, k + dk)];
+                    let offset = Point::new(u - di as f32, v - dj as f32, w - dk as f32);
+
+                    let weight_u = if di == 1 { uu } else { 1.0 - uu };
+                    let weight_v = if dj == 1 { vv } else { 1.0 - vv };
+                    let weight_w = if dk == 1 { ww } else { 1.0 - ww };
+
+                    accumulated += weight_u*weight_v*weight_w*gradient.dot(offset);
+
+                }
+            }
+        }
+
+        return accumulated;
+
+    }
+
+    // fractal Brownian motion, normalized to about [-1:1]
+    pub fn fbm(&self, p: Point, octaves: u32, lacunarity: f32, gain: f32) -> f32 {
+
+        let mut sum = 0.0;
+        let mut norm = 0.0;
+        let mut amplitude = 1.0;
+        let mut p = p;
+
+        for _ in 0..octaves {
+            sum += amplitude*self.noise(p);
+            norm += amplitude;
+            amplitude *= gain;
+            p = p*lacunarity;
+        }
+
+        if norm == 0.0 { return 0.0 }
+
+        return sum / norm;
+
+    }
+
+    // sum of absolute octaves, in [0:1]
+    pub fn turbulence(&self, p: Point, octaves: u32) -> f32 {
+
+        let mut sum = 0.0;
+        let mut norm = 0.0;
+        let mut amplitude = 1.0;
+        let mut p = p;
+
+        for _ in 0..octaves {
+            sum += amplitude*self.noise(p).abs();
+            norm += amplitude;
+            amplitude *= 0.5;
+            p = p*2.0;
+        }
+
+        if norm == 0.0 { return 0.0 }
+
+        return (sum / norm).min(1.0);
+
+    }
+
+    // cellular noise, distances to the closest and second closest feature point
+    // with one feature point per unit cell
+    pub fn worley(&self, p: Point) -> (f32, f32) {
+
+        let (fx, fy, fz) = (p.x().floor(), p.y().floor(), p.z().floor());
+        let (i, j, k) = (fx as i64, fy as i64, fz as i64);
+
+        let mut f1 = f32::INFINITY;
+        let mut f2 = f32::INFINITY;
+
+        for di in -1..=1 {
+            for dj in -1..=1 {
+                for dk in -1..=1 {
+
+                    let (ci, cj, ck) = (i + di, j + dj, k + dk);
+
+                    // feature point in the cell, from three decorrelated hashes
+                    let jitter = Point::new(
+                        self.perm_x[self.hash(ci, cj, ck)] as f32,
+                        self.perm_y[self.hash(ck, ci, cj)] as f32,
+                        self.perm_z[self.hash(cj, ck, ci)] as f32,
+                    ) / (POINT_COUNT as f32);
+                    let feature = Point::new(ci as f32, cj as f32, ck as f32) + jitter;
+
+                    let distance = (feature - p).length();
+
+                    if distance < f1 {
+                        f2 = f1;
+                        f1 = distance;
+                    } else if distance < f2 {
+                        f2 = distance;
+                    }
+
+                }
+            }
+        }
+
+        return (f1, f2);
+
+    }
+
+    // fBm evaluated at a point displaced by a vector-valued fBm
+    pub fn warped(&self, p: Point, strength: f32, octaves: u32) -> f32 {
+
+        let offset = Point::new(
+            self.fbm(p, octaves, 2.0, 0.5),
+            self.fbm(p + Point::new(5.2, 1.3, 2.8), octaves, 2.0, 0.5),
+            self.fbm(p + Point::new(1.7, 9.2, 4.1), octaves, 2.0, 0.5),
+        );
+
+        return self.fbm(p + offset*strength, octaves, 2.0, 0.5);
+
+    }
+
+}
+
+//
+// tests
+#[test]
+fn test_perlin(){
+
+    let perlin = Perlin::new(7);
+
+    // zero on the lattice, deterministic for a seed
+    assert_relative_eq!(perlin.noise(Point::new(3.0, -2.0, 5.0)), 0.0);
+    let p = Point::new(0.3, 1.7, -2.2);
+    assert_relative_eq!(perlin.noise(p), Perlin::new(7).noise(p));
+
+    // continuous and bounded
+    let a = perlin.noise(p);
+    let b = perlin.noise(p + Point::new(1e-3, 0.0, 0.0));
+    assert!((a - b).abs() < 1e-2);
+
+    for i in 0..1000 {
+
+        let p = Point::new(i as f32 * 0.173, i as f32 * 0.311, i as f32 * -0.097);
+
+        assert!(perlin.noise(p).abs() <= 1.0);
+        assert!(perlin.fbm(p, 5, 2.0, 0.5).abs() <= 1.0);
+        assert!((0.0..=1.0).contains(&perlin.turbulence(p, 5)));
+
+        let (f1, f2) = perlin.worley(p);
+        assert!(f1 <= f2);
+        assert!(f1 <= 3.0_f32.sqrt());
+
+        assert!(perlin.warped(p, 4.0, 4).is_finite());
+
+    }
+
+}
@@ -0,0 +1,138 @@
+use crate::hittable::HitRecord;
+use crate::onb::Onb;
+use crate::texture::{Lookup, Texture};
+use crate::vec3::Point;
+
+// world-space step used for the height map finite differences
+const HEIGHT_DELTA: f32 = 1.0 / 1024.0;
+
+// smallest step in texture space, still a few f32 ulps for coordinates near 1
+const MIN_UV_DELTA: f32 = 1e-6;
+
+//
+// normal map enum
+#[derive(Debug, Clone)]
+pub enum NormalMap {
+    // tangent-space normals encoded as rgb in [0:1] (x along dpdu, y along dpdv, z along the normal)
+    Tangent(Texture),
+    // scalar height in the red channel, scaled by a world-space bump strength
+    Height(Texture, f32),
+}
+
+impl NormalMap {
+
+    pub fn shading_normal(&self, record: &HitRecord) -> Point {
+
+        // work with the outward normal, the maps are authored for the outside of the surface
+        let outward = if record.front_face { record.normal } else { -record.normal };
+
+        let perturbed = match self {
+            Self::Tangent(texture) => NormalMap::tangent(texture, record, outward),
+            Self::Height(texture, strength) => NormalMap::height(texture, *strength, record, outward),
+        };
+
+        if perturbed.length_square() < 1e-12 || !perturbed.length_square().is_finite() { return record.shading_normal }
+
+        let perturbed = Point::unit_vector(&perturbed);
+
+        return if record.front_face { perturbed } else { -perturbed };
+
+    }
+
+    fn tangent(texture: &Texture, record: &HitRecord, outward: Point) -> Point {
+
+        let encoded = texture.value(record.u, record.v, record.hit_location);
+        let local = encoded*2.0 - 1.0;
+
+        // dpdu x dpdv is outward, so the bitangent w x u follows dpdv
+        let frame = Onb::from_tangent(outward, record.dpdu);
+
+        return frame.to_world(local);
+
+    }
+
+    fn height(texture: &Texture, strength: f32, record: &HitRecord, outward: Point) -> Point {
+
+        // step both the texture coordinates and the position, so that solid textures bump too
+        let (u, v, p) = (record.u, record.v, record.hit_location);
+        let height = |u: f32, v: f32, p: Point| texture.value(u, v, p).x();
+
+        // the texture-space steps cover about the same distance on the surface whatever the size
+        // of the object, so solid textures on large shapes are not sampled far from the hit
+        // (rounded to the step actually taken, it is only a few ulps on very large objects)
+        let delta = |x: f32, dp: Point| (x + (HEIGHT_DELTA / dp.length()).clamp(MIN_UV_DELTA, HEIGHT_DELTA)) - x;
+        let (du, dv) = (delta(u, record.dpdu), delta(v, record.dpdv));
+
+        let h = height(u, v, p);
+        let dhdu = (height(u + du, v, p + record.dpdu*du) - h) / du * strength;
+        let dhdv = (height(u, v + dv, p + record.dpdv*dv) - h) / dv * strength;
+
+        // derivatives of the displaced surface p + h*n, neglecting the change of n itself
+        let dpdu = record.dpdu + outward*dhdu;
+        let dpdv = record.dpdv + outward*dhdv;
+
+        let normal = dpdu.cross(dpdv);
+
+        // degenerate parameterization, nothing to perturb
+        if normal.length_square() < 1e-12 { return outward }
+
+        return normal;
+
+    }
+
+}
+
+//
+// tests
+#[test]
+fn test_normal_map(){
+
+    use crate::ray::Ray;
+    use crate::texture::{Image, Noise, Pattern};
+    use crate::vec3::Color;
+
+    let ray = Ray::new(Point::new(0.0, 0.0, 2.0), Point::new(0.0, 0.0, -1.0));
+    let record = HitRecord::new(Point::new(0.0, 0.0, 0.0), Point::new(0.0, 0.0, 1.0), 2.0, &ray,
+        0.5, 0.5, Point::new(1.0, 0.0, 0.0), Point::new(0.0, 1.0, 0.0));
+
+    // a flat normal map keeps the geometric normal
+    let flat = NormalMap::Tangent(Texture::Solid(Color::new(0.5, 0.5, 1.0)));
+    assert_eq!(flat.shading_normal(&record), Point::new(0.0, 0.0, 1.0));
+
+    // tilting towards +x in tangent space tilts along dpdu
+    let tilted = NormalMap::Tangent(Texture::Solid(Color::new(1.0, 0.5, 1.0)));
+    let normal = tilted.shading_normal(&record);
+    assert_relative_eq!(normal.x(), normal.z(), epsilon = 1e-6);
+    assert_relative_eq!(normal.length(), 1.0, epsilon = 1e-6);
+
+    // a constant height does not bump
+    let constant = NormalMap::Height(Texture::Solid(Color::new(0.3, 0.3, 0.3)), 1.0);
+    assert_eq!(constant.shading_normal(&record), Point::new(0.0, 0.0, 1.0));
+
+    // a ramp rising along u leans the normal towards -u
+    let ramp = Image::new(2, 1, vec![Color::new(0.0, 0.0, 0.0), Color::new(1.0, 1.0, 1.0)]);
+    let normal = NormalMap::Height(ramp, 0.5).shading_normal(&record);
+    assert!(normal.x() < 0.0);
+    assert_relative_eq!(normal.y(), 0.0, epsilon = 1e-6);
+
+    // solid textures bump the same whatever the length of the tangents, e.g. on a huge sphere
+    let noise = NormalMap::Height(Noise::new(Pattern::Perlin, 4.0, Color::new(0.0, 0.0, 0.0), Color::new(1.0, 1.0, 1.0)), 0.05);
+    let p = Point::new(0.3, 0.7, 0.0);
+    let small = HitRecord::new(p, Point::new(0.0, 0.0, 1.0), 2.0, &ray,
+        0.5, 0.5, Point::new(1.0, 0.0, 0.0), Point::new(0.0, 1.0, 0.0));
+    let large = HitRecord::new(p, Point::new(0.0, 0.0, 1.0), 2.0, &ray,
+        0.5, 0.5, Point::new(1000.0, 0.0, 0.0), Point::new(0.0, 1000.0, 0.0));
+    let (small, large) = (noise.shading_normal(&small), noise.shading_normal(&large));
+    assert!(small.z() < 0.999);
+    assert_relative_eq!(small.x(), large.x(), epsilon = 1e-2);
+    assert_relative_eq!(small.y(), large.y(), epsilon = 1e-2);
+
+    // seen from the inside, the shading normal stays on the side of the ray
+    let ray = Ray::new(Point::new(0.0, 0.0, -2.0), Point::new(0.0, 0.0, 1.0));
+    let record = HitRecord::new(Point::new(0.0, 0.0, 0.0), Point::new(0.0, 0.0, 1.0), 2.0, &ray,
+        0.5, 0.5, Point::new(1.0, 0.0, 0.0), Point::new(0.0, 1.0, 0.0));
+    let normal = tilted.shading_normal(&record);
+    assert!(normal.z() < 0.0);
+    assert!(normal.x() < 0.0);
+
+}
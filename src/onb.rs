@@ -0,0 +1,76 @@
+use crate::vec3::Point;
+
+//
+// orthonormal basis struct
+#[derive(Debug, Clone, Copy)]
+pub struct Onb {
+    u: Point,
+    v: Point,
+    w: Point,
+}
+
+impl Onb {
+
+    pub fn new(w: Point) -> Self {
+
+        // w is assumed to be normalized !!!
+        // any helper axis that is not parallel to w works
+        let helper = if w.x().abs() > 0.9 { Point::new(0.0, 1.0, 0.0) } else { Point::new(1.0, 0.0, 0.0) };
+        let v = Point::unit_vector(&w.cross(helper));
+        let u = v.cross(w);
+
+        Self { u, v, w }
+
+    }
+
+    pub fn from_tangent(w: Point, tangent: Point) -> Self {
+
+        // Gram-Schmidt the tangent against w, falls back to an arbitrary frame
+        // if the tangent is degenerate or parallel to w
+        let u = tangent - w*w.dot(tangent);
+
+        if u.length_square() < 1e-12 { return Self::new(w) }
+
+        let u = Point::unit_vector(&u);
+        let v = w.cross(u);
+
+        Self { u, v, w }
+
+    }
+
+    pub fn u(&self) -> Point {self.u}
+    pub fn v(&self) -> Point {self.v}
+    pub fn w(&self) -> Point {self.w}
+
+    pub fn to_world(&self, a: Point) -> Point {
+        self.u*a.x() + self.v*a.y() + self.w*a.z()
+    }
+
+    pub fn to_local(&self, a: Point) -> Point {
+        Point::new(a.dot(self.u), a.dot(self.v), a.dot(self.w))
+    }
+
+}
+
+//
+// tests
+#[test]
+fn test_onb(){
+
+    let w = Point::unit_vector(&Point::new(1.0, 2.0, -0.5));
+    let onb = Onb::new(w);
+
+    assert_relative_eq!(onb.u().dot(onb.v()), 0.0, epsilon = 1e-6);
+    assert_relative_eq!(onb.u().dot(onb.w()), 0.0, epsilon = 1e-6);
+    assert_relative_eq!(onb.v().dot(onb.w()), 0.0, epsilon = 1e-6);
+    assert_eq!(onb.u().cross(onb.v()), onb.w());
+
+    let a = Point::new(0.3, -0.2, 0.9);
+    let b = onb.to_local(onb.to_world(a));
+    assert_relative_eq!((a - b).length(), 0.0, epsilon = 1e-6);
+
+    let onb = Onb::from_tangent(Point::new(0.0, 0.0, 1.0), Point::new(2.0, 0.0, 1.0));
+    assert_eq!(onb.u(), Point::new(1.0, 0.0, 0.0));
+    assert_eq!(onb.v(), Point::new(0.0, 1.0, 0.0));
+
+}
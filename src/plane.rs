@@ -0,0 +1,85 @@
+use crate::{vec3::Point, ray::Ray, interval::Interval};
+use crate::hittable::{Hittable, HitRecord, HittableObject};
+use crate::material::Material;
+use crate::onb::Onb;
+
+//
+// infinite plane through a point, facing its normal
+pub struct Plane {
+    point: Point,
+    frame: Onb,
+    material: Material,
+}
+
+impl Plane {
+
+    // the surface coordinates are the distances from the point along two tangents, u towards +x
+    // when the plane allows it, so that textures tile once per unit (e.g. a Checker)
+    pub fn new(point: Point, normal: Point, material: Material) -> HittableObject {
+        HittableObject::Plane(Self { point, frame: Onb::from_tangent(Point::unit_vector(&normal), Point::new(1.0, 0.0, 0.0)), material })
+    }
+
+}
+
+impl Hittable for Plane {
+
+    fn hit(&self, ray: &Ray, interval: Interval) -> Option<(HitRecord, &Material)> {
+
+        let normal = self.frame.w();
+        let denominator = normal.dot(ray.direction());
+
+        if denominator.abs() < 1e-8 { return None }
+
+        let root = normal.dot(self.point - ray.origin()) / denominator;
+
+        if ! interval.surrounds(root) { return None }
+
+        let hit_location = ray.at(root);
+        let local = self.frame.to_local(hit_location - self.point);
+
+        let record = HitRecord::new(hit_location, normal, root, ray, local.x(), local.y(), self.frame.u(), self.frame.v());
+
+        return Some((record, &self.material));
+
+    }
+
+}
+
+//
+// tests
+#[test]
+fn test_plane(){
+
+    use crate::hittable::HittableList;
+    use crate::material::{DiffuseLight, Lambertian};
+
+    let material = Lambertian::new(Point::new(0.5, 0.5, 0.5));
+    let plane = Plane::new(Point::new(0.0, -0.5, 0.0), Point::new(0.0, 3.0, 0.0), material);
+    let interval = Interval::universe().set_min(0.001);
+
+    // far away hits stay on the plane, unlike the ones of a huge sphere
+    let ray = Ray::new(Point::new(0.0, 1.5, 0.0), Point::new(1000.0, -2.0, 500.0));
+    let (record, _) = plane.hit(&ray, interval).expect("The ray hits the plane.");
+
+    assert_relative_eq!(record.t, 1.0);
+    assert_relative_eq!(record.hit_location.y(), -0.5);
+    assert!(record.front_face);
+    assert_eq!(record.normal, Point::new(0.0, 1.0, 0.0));
+
+    // planar coordinates in world units, u along +x
+    assert_relative_eq!(record.u, 1000.0);
+    assert_relative_eq!(record.v.abs(), 500.0);
+    assert!(record.dpdu.cross(record.dpdv).dot(record.normal) > 0.0);
+
+    // from below, and parallel or pointing away
+    let ray = Ray::new(Point::new(0.0, -2.0, 0.0), Point::new(0.0, 1.0, 0.0));
+    assert!(!plane.hit(&ray, interval).expect("The ray hits the plane.").0.front_face);
+    assert!(plane.hit(&Ray::new(Point::default(), Point::new(1.0, 0.0, 0.0)), interval).is_none());
+    assert!(plane.hit(&Ray::new(Point::default(), Point::new(0.0, 1.0, 0.0)), interval).is_none());
+
+    // an emitting plane would have infinite power, it gets no light and only glows where it is hit
+    let mut world = HittableList::new();
+    let glow = world.add(Plane::new(Point::default(), Point::new(0.0, 1.0, 0.0), DiffuseLight::new(Point::new(1.0, 1.0, 1.0))));
+    assert!(world.emitter_lights(glow).is_empty() && world.lights().is_empty());
+
+}
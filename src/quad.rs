@@ -0,0 +1,103 @@
+use crate::{vec3::Point, ray::Ray, interval::Interval};
+use crate::hittable::{Hittable, HitRecord, HittableObject};
+use crate::light::{AreaLight, Light};
+use crate::material::Material;
+
+//
+// parallelogram spanned by u and v from a corner, facing u x v
+pub struct Quad {
+    corner: Point,
+    u: Point,
+    v: Point,
+    // u x v over its squared length, to get the plane coordinates of a point
+    w: Point,
+    normal: Point,
+    // plane offset, normal . p = d
+    d: f32,
+    material: Material,
+}
+
+impl Quad {
+
+    pub fn new(corner: Point, u: Point, v: Point, material: Material) -> HittableObject {
+        HittableObject::Quad(Quad::side(corner, u, v, material))
+    }
+
+    // the bare quad, for the shapes made of several
+    pub(crate) fn side(corner: Point, u: Point, v: Point, material: Material) -> Self {
+
+        let n = u.cross(v);
+        let normal = Point::unit_vector(&n);
+        let w = n / n.length_square();
+
+        Self { corner, u, v, w, normal, d: normal.dot(corner), material }
+
+    }
+
+    pub fn area_light(&self) -> Option<Light> {
+        self.material.emission().map(|radiance| AreaLight::quad(self.corner, self.u, self.v, radiance))
+    }
+
+}
+
+impl Hittable for Quad {
+
+    fn hit(&self, ray: &Ray, interval: Interval) -> Option<(HitRecord, &Material)> {
+
+        // rays parallel to the plane miss it
+        let denominator = self.normal.dot(ray.direction());
+
+        if denominator.abs() < 1e-8 { return None }
+
+        let root = (self.d - self.normal.dot(ray.origin())) / denominator;
+
+        if ! interval.surrounds(root) { return None }
+
+        // plane coordinates (alpha, beta) of the hit, inside the quad in [0:1]^2
+        let hit_location = ray.at(root);
+        let planar = hit_location - self.corner;
+        let alpha = self.w.dot(planar.cross(self.v));
+        let beta = self.w.dot(self.u.cross(planar));
+
+        if !(0.0..=1.0).contains(&alpha) || !(0.0..=1.0).contains(&beta) { return None }
+
+        let record = HitRecord::new(hit_location, self.normal, root, ray, alpha, beta, self.u, self.v);
+
+        return Some((record, &self.material));
+
+    }
+
+}
+
+//
+// tests
+#[test]
+fn test_quad(){
+
+    use crate::material::Lambertian;
+
+    let material = Lambertian::new(Point::new(0.5, 0.5, 0.5));
+    let quad = Quad::new(Point::new(-1.0, -1.0, 0.0), Point::new(2.0, 0.0, 0.0), Point::new(0.0, 4.0, 0.0), material);
+    let interval = Interval::universe().set_min(0.001);
+
+    // facing +z, the coordinates run along the edges
+    let ray = Ray::new(Point::new(0.5, 2.0, 3.0), Point::new(0.0, 0.0, -1.0));
+    let (record, _) = quad.hit(&ray, interval).expect("The ray hits the quad.");
+
+    assert_relative_eq!(record.t, 3.0);
+    assert!(record.front_face);
+    assert_eq!(record.normal, Point::new(0.0, 0.0, 1.0));
+    assert_relative_eq!(record.u, 0.75);
+    assert_relative_eq!(record.v, 0.75);
+    assert!(record.dpdu.cross(record.dpdv).dot(record.normal) > 0.0);
+
+    // seen from behind
+    let ray = Ray::new(Point::new(0.5, 2.0, -3.0), Point::new(0.0, 0.0, 1.0));
+    let (record, _) = quad.hit(&ray, interval).expect("The ray hits the quad.");
+    assert!(!record.front_face);
+
+    // outside the edges, and parallel to the plane
+    assert!(quad.hit(&Ray::new(Point::new(1.5, 0.0, 3.0), Point::new(0.0, 0.0, -1.0)), interval).is_none());
+    assert!(quad.hit(&Ray::new(Point::new(0.0, 0.0, 1.0), Point::new(1.0, 0.0, 0.0)), interval).is_none());
+
+}
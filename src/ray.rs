@@ -1,4 +1,16 @@
-use crate::vec3::Point;
+use crate::spectrum::Wavelengths;
+use crate::vec3::{Color, Point};
+
+//
+// what a ray is traced for, the objects can be hidden from some of them
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum RayKind {
+    Camera,
+    // scattered back to the side the ray came from, or through the surface
+    Reflection,
+    Refraction,
+    Shadow,
+}
 
 //
 // Ray struct
@@ -6,12 +18,36 @@ use crate::vec3::Point;
 pub struct Ray {
     origin: Point,
     direction: Point,
+    wavelengths: Option<Wavelengths>,
+    kind: RayKind,
 }
 
 impl Ray {
 
     pub fn new(origin: Point, direction: Point) -> Self {
-        Self{origin, direction}
+        Self{origin, direction, wavelengths: None, kind: RayKind::Camera}
+    }
+
+    pub fn set_kind(self, kind: RayKind) -> Self {
+        Self { kind, ..self }
+    }
+
+    pub fn kind(&self) -> RayKind {self.kind}
+
+    // rays of the spectral renderer carry their wavelengths, rgb rays carry none
+    pub fn set_wavelengths(self, wavelengths: Option<Wavelengths>) -> Self {
+        Self { wavelengths, ..self }
+    }
+
+    pub fn wavelengths(&self) -> Option<Wavelengths> {self.wavelengths}
+
+    // a color in the space the ray is rendered in: unchanged for rgb rays,
+    // upsampled to the carried wavelengths for spectral rays
+    pub fn spectrum(&self, color: Color) -> Color {
+        match &self.wavelengths {
+            Some(w) => w.upsample(color),
+            None => color,
+        }
     }
 
     pub fn origin(&self) -> Point {self.origin}
@@ -35,4 +71,15 @@ fn test_ray(){
     assert_eq!(ray.at(-1.0), Point::new(0.5, -1.0, -3.0));
     assert_eq!(ray.origin(), orig);
     assert_eq!(ray.direction(), dir);
+
+    let color = Color::new(0.2, 0.4, 0.6);
+    assert_eq!(ray.spectrum(color), color);
+
+    let wavelengths = Wavelengths::sample(0.3);
+    let ray = ray.set_wavelengths(Some(wavelengths));
+    assert_eq!(ray.wavelengths(), Some(wavelengths));
+    assert_eq!(ray.spectrum(color), wavelengths.upsample(color));
+
+    assert_eq!(ray.kind(), RayKind::Camera);
+    assert_eq!(ray.set_kind(RayKind::Shadow).kind(), RayKind::Shadow);
 }
@@ -1,7 +1,22 @@
 use crate::camera::Camera;
-use crate::hittable::HittableList;
-use crate::material::{Dielectric, Lambertian, Metal};
+use crate::constant_medium::ConstantMedium;
+use crate::cuboid::Cuboid;
+use crate::disk::Disk;
+use crate::grid_volume::GridVolume;
+use crate::hittable::{HittableList, LightLinks, Visibility};
+use crate::ies::IesProfile;
+use crate::light::{DirectionalLight, PointLight, SpotLight};
+use crate::light_sampler::LightSampling;
+use crate::material::{Dielectric, DiffuseLight, Lambertian, Layered, Mapped, Metal, Mix, OrenNayar, Principled, ThinFilm};
+use crate::medium::{Grid, Phase};
+use crate::noise::Perlin;
+use crate::normal_map::NormalMap;
+use crate::plane::Plane;
+use crate::quad::Quad;
+use crate::sky::Sky;
+use crate::spectrum::Dispersion;
 use crate::sphere::Sphere;
+use crate::texture::{Checker, Noise, Pattern};
 use crate::vec3::{Color, Point};
 
 pub fn final_scene() -> (HittableList, Camera) {
@@ -10,7 +25,7 @@ pub fn final_scene() -> (HittableList, Camera) {
     let mut world = HittableList::new();
 
     let material_ground = Lambertian::new(Color::new(0.5, 0.5, 0.5));
-    world.add(Sphere::new(Point::new(0.0, -1000.0, 0.0), 1000.0, material_ground));
+    world.add(Plane::new(Point::new(0.0, 0.0, 0.0), Point::new(0.0, 1.0, 0.0), material_ground));
 
     for a in -11..11 {
         for b in -11..11 {
@@ -82,10 +97,12 @@ pub fn penultimate_scene() -> (HittableList, Camera) {
     let material_ground = Lambertian::new(Color::new(0.1, 0.6, 0.1));
     let material_center = Lambertian::new(Color::new(0.6, 0.0, 0.0));
     let material_left = Dielectric::new(1.50);
-    let material_bubble = Dielectric::new(1.0 / 1.50);
+    // an air bubble in the glass behind a swirling water film, indices are relative to the glass
+    let swirl = Noise::new(Pattern::Warped, 2.0, Color::new(250.0, 250.0, 250.0), Color::new(700.0, 700.0, 700.0));
+    let material_bubble = Dielectric::filmed(1.0 / 1.50, ThinFilm::textured(swirl, 1.33 / 1.50));
     let material_right = Metal::new(Color::new(0.05, 0.05, 0.80), 0.2);
 
-    world.add(Sphere::new(Point::new(0.0, -100.5, -1.0), 100.0, material_ground));
+    world.add(Plane::new(Point::new(0.0, -0.5, 0.0), Point::new(0.0, 1.0, 0.0), material_ground));
     world.add(Sphere::new(Point::new(0.0, 0.0, -1.2), 0.5, material_center));
     world.add(Sphere::new(Point::new(-1.0, 0.0, -1.0), 0.5, material_left));
     world.add(Sphere::new(Point::new(-1.0, 0.0, -1.0), 0.4, material_bubble));
@@ -111,3 +128,424 @@ pub fn penultimate_scene() -> (HittableList, Camera) {
     return (world, camera);
 
 }
+
+pub fn procedural_scene() -> (HittableList, Camera) {
+
+    // world
+    let mut world = HittableList::new();
+
+    let stone = Noise::new(Pattern::Stone, 1.5, Color::new(0.05, 0.05, 0.05), Color::new(0.55, 0.52, 0.48));
+    let material_ground = Mapped::new(OrenNayar::textured(stone.clone(), 0.8), NormalMap::Height(stone, 0.02));
+    world.add(Plane::new(Point::new(0.0, 0.0, 0.0), Point::new(0.0, 1.0, 0.0), material_ground));
+
+    let marble = Noise::new(Pattern::Marble, 4.0, Color::new(0.25, 0.25, 0.3), Color::new(0.95, 0.95, 0.92));
+    world.add(Sphere::new(Point::new(-4.0, 1.0, 0.0), 1.0, Lambertian::textured(marble)));
+
+    let wood = Noise::new(Pattern::Wood, 3.0, Color::new(0.45, 0.25, 0.1), Color::new(0.7, 0.45, 0.22));
+    let varnished = Layered::tinted(Lambertian::textured(wood), 1.5, 0.05, Color::new(0.95, 0.85, 0.65));
+    world.add(Sphere::new(Point::new(0.0, 1.0, 0.0), 1.0, varnished));
+
+    let bumps = Noise::new(Pattern::Fbm, 6.0, Color::new(0.0, 0.0, 0.0), Color::new(1.0, 1.0, 1.0));
+    let material = Mapped::new(Metal::new(Color::new(0.7, 0.6, 0.5), 0.0), NormalMap::Height(bumps, 0.05));
+    let rust = Noise::new(Pattern::Turbulence, 1.5, Color::new(0.0, 0.0, 0.0), Color::new(1.0, 1.0, 1.0));
+    let material = Mix::textured(material, Lambertian::new(Color::new(0.35, 0.12, 0.05)), rust);
+    world.add(Sphere::new(Point::new(4.0, 1.0, 0.0), 1.0, material));
+
+    // camera
+    let aspect_ratio = 16.0/9.0;
+    let image_width = 1200;
+    let samples_per_pixel = 500;
+    let max_depth = 50;
+
+    let v_fov = 20.0;
+    let look_from = Point::new(13.0, 2.0, 3.0);
+    let look_at = Point::new(0.0, 0.0, 0.0);
+    let v_up = Point::new(0.0, 1.0, 0.0);
+
+    let defocus_angle = 0.0;
+    let focus_distance = 10.0;
+
+    let camera = Camera::new(aspect_ratio, image_width, samples_per_pixel, max_depth,
+         v_fov, look_from, look_at, v_up, defocus_angle, focus_distance);
+
+    return (world, camera);
+
+}
+
+pub fn dispersion_scene() -> (HittableList, Camera) {
+
+    // world
+    let mut world = HittableList::new();
+
+    let material_ground = Lambertian::new(Color::new(0.8, 0.8, 0.8));
+    world.add(Plane::new(Point::new(0.0, -0.5, 0.0), Point::new(0.0, 1.0, 0.0), material_ground));
+
+    world.add(Sphere::new(Point::new(-1.0, 0.0, -1.0), 0.5, Dielectric::dispersive(Dispersion::bk7())));
+    world.add(Sphere::new(Point::new(0.0, 0.0, -1.2), 0.5, Dielectric::dispersive(Dispersion::diamond())));
+    world.add(Sphere::new(Point::new(1.0, 0.0, -1.0), 0.5, Dielectric::dispersive(Dispersion::dense_flint())));
+
+    // camera
+    let aspect_ratio = 16.0/9.0;
+    let image_width = 1200;
+    let samples_per_pixel = 500;
+    let max_depth = 50;
+
+    let v_fov = 20.0;
+    let look_from = Point::new(-2.0, 2.0, 1.0);
+    let look_at = Point::new(0.0, 0.0, -1.0);
+    let v_up = Point::new(0.0, 1.0, 0.0);
+
+    let defocus_angle = 0.0;
+    let focus_distance = 3.4;
+
+    let mut camera = Camera::new(aspect_ratio, image_width, samples_per_pixel, max_depth,
+         v_fov, look_from, look_at, v_up, defocus_angle, focus_distance);
+    camera.spectral = true;
+
+    return (world, camera);
+
+}
+
+pub fn volume_scene() -> (HittableList, Camera) {
+
+    // world
+    let mut world = HittableList::new();
+
+    let material_ground = Lambertian::new(Color::new(0.5, 0.5, 0.5));
+    world.add(Plane::new(Point::new(0.0, -0.5, 0.0), Point::new(0.0, 1.0, 0.0), material_ground));
+
+    let perlin = Perlin::new(1);
+
+    // cloud, a noisy ball of strongly forward scattering droplets
+    let (min, max) = (Point::new(-1.6, -0.45, -1.6), Point::new(-0.4, 0.75, -0.4));
+    let center = (min + max) / 2.0;
+    let density = Grid::from_fn([48, 48, 48], min, max, |p| {
+        let falloff = 1.0 - 2.0*(p - center).length() / (max.x() - min.x());
+        2.0*(falloff + 0.6*perlin.fbm(p*4.0, 5, 2.0, 0.5)).max(0.0)
+    });
+    let cloud = Grid::new([48, 48, 48], density, min, max, 10.0, Color::new(0.95, 0.95, 0.95), Phase::HenyeyGreenstein(0.5));
+    world.add(GridVolume::new(cloud));
+
+    // fire, a flickering cone of soot glowing with its squared density
+    let (min, max) = (Point::new(-0.4, -0.45, -1.6), Point::new(0.4, 0.75, -0.8));
+    let flame = |p: Point| {
+        let q = p - Point::new(0.0, min.y(), -1.2);
+        let radius = (q.x()*q.x() + q.z()*q.z()).sqrt();
+        (4.0*(0.3 - radius - 0.2*q.y()) + 0.5*perlin.fbm(p*6.0, 4, 2.0, 0.5)).max(0.0)
+    };
+    let density = Grid::from_fn([32, 48, 32], min, max, flame);
+    let emission = Grid::from_fn([32, 48, 32], min, max, |p| 3.0*flame(p)*flame(p));
+    let fire = Grid::new([32, 48, 32], density, min, max, 6.0, Color::new(0.2, 0.2, 0.2), Phase::Isotropic)
+        .set_emission(emission, Color::new(4.0, 1.5, 0.3));
+    world.add(GridVolume::new(fire));
+
+    // smoke, a dark constant density ball
+    let boundary = Sphere::new(Point::new(1.0, 0.0, -1.0), 0.5, Lambertian::new(Color::default()));
+    world.add(ConstantMedium::new(boundary, 4.0, Color::new(0.3, 0.3, 0.3), Phase::Isotropic));
+
+    // camera
+    let aspect_ratio = 16.0/9.0;
+    let image_width = 1200;
+    let samples_per_pixel = 500;
+    let max_depth = 50;
+
+    let v_fov = 20.0;
+    let look_from = Point::new(-2.0, 2.0, 1.0);
+    let look_at = Point::new(0.0, 0.0, -1.0);
+    let v_up = Point::new(0.0, 1.0, 0.0);
+
+    let defocus_angle = 0.0;
+    let focus_distance = 3.4;
+
+    let camera = Camera::new(aspect_ratio, image_width, samples_per_pixel, max_depth,
+         v_fov, look_from, look_at, v_up, defocus_angle, focus_distance);
+
+    return (world, camera);
+
+}
+
+pub fn lights_scene() -> (HittableList, Camera) {
+
+    // world
+    let mut world = HittableList::new();
+
+    let material_ground = OrenNayar::new(Color::new(0.6, 0.6, 0.6), 0.5);
+    world.add(Plane::new(Point::new(0.0, 0.0, 0.0), Point::new(0.0, 1.0, 0.0), material_ground));
+
+    world.add(Sphere::new(Point::new(-4.0, 1.0, 0.0), 1.0, OrenNayar::new(Color::new(0.7, 0.45, 0.3), 0.8)));
+    world.add(Sphere::new(Point::new(0.0, 1.0, 0.0), 1.0, Principled::new(Color::new(0.6, 0.05, 0.05), 0.0, 0.3)));
+    let gold = world.add(Sphere::new(Point::new(4.0, 1.0, 0.0), 1.0, Metal::gold(0.3)));
+
+    // a warm bulb between the spheres, a cold spotlight from above and faint moonlight
+    world.add_light(PointLight::new(Point::new(2.0, 1.5, 2.0), Color::new(6.0, 4.5, 3.0)));
+    world.add_light(SpotLight::new(Point::new(-3.0, 6.0, 2.0), Point::new(-2.0, 0.0, 0.0), Color::new(20.0, 24.0, 30.0), 40.0, 0.3));
+    world.add_light(DirectionalLight::new(Point::new(-1.0, 2.0, -1.0), Color::new(0.08, 0.1, 0.15), 0.53));
+
+    // a fill panel next to the camera, unseen by it, casting no shadows and kept out of the gold
+    let fill = world.add(Quad::new(Point::new(9.0, 0.5, 5.0), Point::new(0.0, 3.0, 0.0), Point::new(0.0, 0.0, -4.0), DiffuseLight::new(Color::new(0.4, 0.4, 0.4))));
+    world.set_visibility(fill, Visibility { camera: false, shadow: false, ..Visibility::default() });
+    for light in world.emitter_lights(fill).to_vec() { world.set_links(light, LightLinks::except(&[gold])) }
+
+    // camera
+    let aspect_ratio = 16.0/9.0;
+    let image_width = 1200;
+    let samples_per_pixel = 500;
+    let max_depth = 50;
+
+    let v_fov = 20.0;
+    let look_from = Point::new(13.0, 2.0, 3.0);
+    let look_at = Point::new(0.0, 0.0, 0.0);
+    let v_up = Point::new(0.0, 1.0, 0.0);
+
+    let defocus_angle = 0.0;
+    let focus_distance = 10.0;
+
+    let mut camera = Camera::new(aspect_ratio, image_width, samples_per_pixel, max_depth,
+         v_fov, look_from, look_at, v_up, defocus_angle, focus_distance);
+    camera.background = Some(Color::new(0.01, 0.01, 0.02));
+
+    return (world, camera);
+
+}
+
+pub fn area_lights_scene() -> (HittableList, Camera) {
+
+    // world
+    let mut world = HittableList::new();
+
+    let material_ground = Lambertian::new(Color::new(0.5, 0.5, 0.5));
+    world.add(Plane::new(Point::new(0.0, 0.0, 0.0), Point::new(0.0, 1.0, 0.0), material_ground));
+
+    world.add(Sphere::new(Point::new(-4.0, 1.0, 0.0), 1.0, OrenNayar::new(Color::new(0.7, 0.45, 0.3), 0.8)));
+    world.add(Sphere::new(Point::new(0.0, 1.0, 0.0), 1.0, Principled::new(Color::new(0.6, 0.05, 0.05), 0.0, 0.0)));
+    world.add(Sphere::new(Point::new(4.0, 1.0, 0.0), 1.0, Metal::gold(0.2)));
+
+    // a softbox overhead, a round panel from the side and a small glowing ball,
+    // the emitters are sampled as area lights
+    world.add(Quad::new(Point::new(-1.0, 5.0, -3.0), Point::new(4.0, 0.0, 0.0), Point::new(0.0, 0.0, 4.0), DiffuseLight::new(Color::new(4.0, 4.0, 4.0))));
+    world.add(Disk::new(Point::new(6.0, 2.0, 5.0), Point::new(-1.0, -0.3, -1.0), 1.0, DiffuseLight::new(Color::new(3.0, 5.0, 8.0))));
+    world.add(Sphere::new(Point::new(2.0, 0.3, 2.0), 0.3, DiffuseLight::new(Color::new(12.0, 7.0, 3.0))));
+
+    // camera
+    let aspect_ratio = 16.0/9.0;
+    let image_width = 1200;
+    let samples_per_pixel = 500;
+    let max_depth = 50;
+
+    let v_fov = 20.0;
+    let look_from = Point::new(13.0, 2.0, 3.0);
+    let look_at = Point::new(0.0, 0.0, 0.0);
+    let v_up = Point::new(0.0, 1.0, 0.0);
+
+    let defocus_angle = 0.0;
+    let focus_distance = 10.0;
+
+    let mut camera = Camera::new(aspect_ratio, image_width, samples_per_pixel, max_depth,
+         v_fov, look_from, look_at, v_up, defocus_angle, focus_distance);
+    camera.background = Some(Color::new(0.01, 0.01, 0.02));
+
+    return (world, camera);
+
+}
+
+pub fn many_lights_scene() -> (HittableList, Camera) {
+
+    // world
+    let mut world = HittableList::new();
+
+    let material_ground = Lambertian::new(Color::new(0.5, 0.5, 0.5));
+    world.add(Plane::new(Point::new(0.0, 0.0, 0.0), Point::new(0.0, 1.0, 0.0), material_ground));
+
+    // the small spheres of the final scene, a third of them glowing
+    for a in -11..11 {
+        for b in -11..11 {
+
+            let choose_material = Point::random_float();
+            let center = Point::new(a as f32 + 0.9*Point::random_float(), 0.2, b as f32 + 0.9*Point::random_float());
+
+            if (center - Point::new(4.0, 0.2, 0.0)).length() > 0.9 {
+
+                if choose_material < 0.35 {
+
+                    let radiance = (Color::random_vec()*0.7 + 0.3)*4.0;
+                    world.add(Sphere::new(center, 0.2, DiffuseLight::new(radiance)));
+
+                } else if choose_material < 0.8 {
+
+                    let albedo = Color::random_vec() * Color::random_vec();
+                    world.add(Sphere::new(center, 0.2, Lambertian::new(albedo)));
+
+                } else {
+
+                    let albedo = Color::random_vec()*0.5 + 0.5;
+                    let fuzz = Point::random_float()*0.5;
+                    world.add(Sphere::new(center, 0.2, Metal::new(albedo, fuzz)));
+
+                }
+            }
+
+        }
+    }
+
+    let material = Dielectric::new(1.5);
+    world.add(Sphere::new(Point::new(0.0, 1.0, 0.0), 1.0, material));
+
+    let material = Lambertian::new(Color::new(0.4, 0.2, 0.1));
+    world.add(Sphere::new(Point::new(-4.0, 1.0, 0.0), 1.0, material));
+
+    let material = Metal::new(Color::new(0.7, 0.6, 0.5), 0.0);
+    world.add(Sphere::new(Point::new(4.0, 1.0, 0.0), 1.0, material));
+
+    // camera
+    let aspect_ratio = 16.0/9.0;
+    let image_width = 1200;
+    let samples_per_pixel = 500;
+    let max_depth = 50;
+
+    let v_fov = 20.0;
+    let look_from = Point::new(13.0, 2.0, 3.0);
+    let look_at = Point::new(0.0, 0.0, 0.0);
+    let v_up = Point::new(0.0, 1.0, 0.0);
+
+    let defocus_angle = 0.6;
+    let focus_distance = 10.0;
+
+    let mut camera = Camera::new(aspect_ratio, image_width, samples_per_pixel, max_depth,
+         v_fov, look_from, look_at, v_up, defocus_angle, focus_distance);
+    camera.background = Some(Color::new(0.0, 0.0, 0.0));
+    // one shadow ray per hit instead of one per light
+    camera.light_sampling = LightSampling::Tree;
+
+    return (world, camera);
+
+}
+
+pub fn ies_scene() -> (HittableList, Camera) {
+
+    // world
+    let mut world = HittableList::new();
+
+    let material_ground = Lambertian::new(Color::new(0.5, 0.5, 0.5));
+    world.add(Plane::new(Point::new(0.0, 0.0, 0.0), Point::new(0.0, 1.0, 0.0), material_ground));
+
+    // a wall behind three spheres
+    let material_wall = Lambertian::new(Color::new(0.8, 0.8, 0.75));
+    world.add(Quad::new(Point::new(-8.0, 0.0, -3.0), Point::new(16.0, 0.0, 0.0), Point::new(0.0, 6.0, 0.0), material_wall));
+
+    world.add(Sphere::new(Point::new(-3.0, 0.7, -1.0), 0.7, OrenNayar::new(Color::new(0.7, 0.3, 0.2), 0.6)));
+    world.add(Sphere::new(Point::new(0.0, 0.7, -1.0), 0.7, Metal::new(Color::new(0.8, 0.8, 0.8), 0.1)));
+    world.add(Sphere::new(Point::new(3.0, 0.7, -1.0), 0.7, Lambertian::new(Color::new(0.2, 0.4, 0.7))));
+
+    // recessed downlights over the spheres and wall washers aimed along the wall
+    let downlight = IesProfile::parse(include_str!("../media/ies/downlight.ies")).expect("Valid IES file.");
+    let wallwash = IesProfile::parse(include_str!("../media/ies/wallwash.ies")).expect("Valid IES file.");
+
+    for x in [-3.0, 0.0, 3.0] {
+        world.add_light(PointLight::new(Point::new(x, 4.0, -1.0), Color::new(20.0, 18.0, 15.0)).set_profile(downlight.clone()));
+        world.add_light(PointLight::new(Point::new(x + 1.5, 5.5, -1.5), Color::new(8.0, 8.0, 8.0)).set_profile(wallwash.clone()));
+    }
+
+    // camera
+    let aspect_ratio = 16.0/9.0;
+    let image_width = 1200;
+    let samples_per_pixel = 500;
+    let max_depth = 50;
+
+    let v_fov = 40.0;
+    let look_from = Point::new(0.0, 2.5, 9.0);
+    let look_at = Point::new(0.0, 1.5, -1.0);
+    let v_up = Point::new(0.0, 1.0, 0.0);
+
+    let defocus_angle = 0.0;
+    let focus_distance = 10.0;
+
+    let mut camera = Camera::new(aspect_ratio, image_width, samples_per_pixel, max_depth,
+         v_fov, look_from, look_at, v_up, defocus_angle, focus_distance);
+    camera.background = Some(Color::new(0.0, 0.0, 0.0));
+
+    return (world, camera);
+
+}
+
+pub fn sky_scene() -> (HittableList, Camera) {
+
+    // late afternoon sun behind the camera, off to the side
+    let sky = Sky::new(25.0, 30.0, 3.0);
+
+    // world
+    let mut world = HittableList::new();
+
+    let material_ground = Lambertian::textured(Checker::new(0.5, Color::new(0.55, 0.55, 0.5), Color::new(0.3, 0.3, 0.28)));
+    world.add(Plane::new(Point::new(0.0, 0.0, 0.0), Point::new(0.0, 1.0, 0.0), material_ground));
+
+    world.add(Sphere::new(Point::new(0.0, 1.0, 0.0), 1.0, Dielectric::new(1.5)));
+    world.add(Sphere::new(Point::new(-4.0, 1.0, 0.0), 1.0, OrenNayar::new(Color::new(0.7, 0.45, 0.3), 0.5)));
+    world.add(Sphere::new(Point::new(4.0, 1.0, 0.0), 1.0, Metal::new(Color::new(0.9, 0.9, 0.9), 0.0)));
+
+    world.add_light(sky.sun());
+
+    // camera
+    let aspect_ratio = 16.0/9.0;
+    let image_width = 1200;
+    let samples_per_pixel = 500;
+    let max_depth = 50;
+
+    let v_fov = 30.0;
+    let look_from = Point::new(13.0, 2.0, 3.0);
+    let look_at = Point::new(0.0, 1.5, 0.0);
+    let v_up = Point::new(0.0, 1.0, 0.0);
+
+    let defocus_angle = 0.0;
+    let focus_distance = 10.0;
+
+    let mut camera = Camera::new(aspect_ratio, image_width, samples_per_pixel, max_depth,
+         v_fov, look_from, look_at, v_up, defocus_angle, focus_distance);
+    camera.sky = Some(sky);
+
+    return (world, camera);
+
+}
+
+pub fn cornell_box_scene() -> (HittableList, Camera) {
+
+    // world
+    let mut world = HittableList::new();
+
+    let red = Lambertian::new(Color::new(0.65, 0.05, 0.05));
+    let white = Lambertian::new(Color::new(0.73, 0.73, 0.73));
+    let green = Lambertian::new(Color::new(0.12, 0.45, 0.15));
+    let light = DiffuseLight::new(Color::new(15.0, 15.0, 15.0));
+
+    // walls, floor and ceiling, with the light facing down just below it
+    world.add(Quad::new(Point::new(555.0, 0.0, 0.0), Point::new(0.0, 555.0, 0.0), Point::new(0.0, 0.0, 555.0), green));
+    world.add(Quad::new(Point::new(0.0, 0.0, 0.0), Point::new(0.0, 555.0, 0.0), Point::new(0.0, 0.0, 555.0), red));
+    world.add(Quad::new(Point::new(343.0, 554.0, 332.0), Point::new(-130.0, 0.0, 0.0), Point::new(0.0, 0.0, -105.0), light));
+    world.add(Quad::new(Point::new(0.0, 0.0, 0.0), Point::new(555.0, 0.0, 0.0), Point::new(0.0, 0.0, 555.0), white.clone()));
+    world.add(Quad::new(Point::new(555.0, 555.0, 555.0), Point::new(-555.0, 0.0, 0.0), Point::new(0.0, 0.0, -555.0), white.clone()));
+    world.add(Quad::new(Point::new(0.0, 0.0, 555.0), Point::new(555.0, 0.0, 0.0), Point::new(0.0, 555.0, 0.0), white.clone()));
+
+    // the tall and the short block
+    world.add(Cuboid::rotated(Point::new(366.0, 0.0, 353.3), Point::new(165.0, 330.0, 165.0), 15.0, white.clone()));
+    world.add(Cuboid::rotated(Point::new(183.0, 0.0, 169.0), Point::new(165.0, 165.0, 165.0), -18.0, white));
+
+    // camera
+    let aspect_ratio = 1.0;
+    let image_width = 600;
+    let samples_per_pixel = 500;
+    let max_depth = 50;
+
+    let v_fov = 40.0;
+    let look_from = Point::new(278.0, 278.0, -800.0);
+    let look_at = Point::new(278.0, 278.0, 0.0);
+    let v_up = Point::new(0.0, 1.0, 0.0);
+
+    let defocus_angle = 0.0;
+    let focus_distance = 10.0;
+
+    let mut camera = Camera::new(aspect_ratio, image_width, samples_per_pixel, max_depth,
+         v_fov, look_from, look_at, v_up, defocus_angle, focus_distance);
+    camera.background = Some(Color::new(0.0, 0.0, 0.0));
+
+    return (world, camera);
+
+}
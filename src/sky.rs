@@ -0,0 +1,158 @@
+use crate::light::{DirectionalLight, Light};
+use crate::vec3::{Color, Point};
+
+use std::f32::consts::PI;
+
+// radiance of the scene per kcd/m^2 of sky luminance, so that a clear sky at noon stays below one
+const LUMINANCE_SCALE: f32 = 0.025;
+
+// illuminance of the sun outside the atmosphere, in klux
+const SUN_ILLUMINANCE: f32 = 128.0;
+
+// apparent diameter of the sun, in degrees
+const SUN_DIAMETER: f32 = 0.53;
+
+//
+// Sky, the analytic daylight model of Preetham, Shirley and Smits (1999)
+#[derive(Debug, Clone)]
+pub struct Sky {
+
+    sun_direction: Point,
+    // zenith angle of the sun
+    theta_s: f32,
+    // zenith values and Perez coefficients of the luminance Y and the chromaticities x and y
+    zenith: [f32; 3],
+    perez: [[f32; 5]; 3],
+    // irradiance of the sun on a surface facing it, after going through the atmosphere
+    sun_irradiance: Color,
+
+}
+
+impl Sky {
+
+    // elevation of the sun above the horizon in degrees, clamped to [0:90] as the model has no
+    // night; azimuth in degrees around the y-axis, 0 towards +x and 90 towards -z; turbidity in
+    // [2:10], from a very clear sky to a hazy one
+    pub fn new(elevation: f32, azimuth: f32, turbidity: f32) -> Self {
+
+        let t = turbidity.clamp(2.0, 10.0);
+        let theta_s = (90.0 - elevation.clamp(0.0, 90.0)).to_radians();
+        let (elevation, azimuth) = (elevation.to_radians(), azimuth.to_radians());
+        let sun_direction = Point::new(elevation.cos()*azimuth.cos(), elevation.sin(), -elevation.cos()*azimuth.sin());
+
+        // zenith luminance in kcd/m^2 and chromaticities
+        let chi = (4.0/9.0 - t/120.0)*(PI - 2.0*theta_s);
+        let luminance = (4.0453*t - 4.9710)*chi.tan() - 0.2155*t + 2.4192;
+
+        let (t1, t2, t3) = (theta_s, theta_s*theta_s, theta_s*theta_s*theta_s);
+        let x = t*t*(0.00166*t3 - 0.00375*t2 + 0.00209*t1)
+            + t*(-0.02903*t3 + 0.06377*t2 - 0.03202*t1 + 0.00394)
+            + (0.11693*t3 - 0.21196*t2 + 0.06052*t1 + 0.25886);
+        let y = t*t*(0.00275*t3 - 0.00610*t2 + 0.00317*t1)
+            + t*(-0.04214*t3 + 0.08970*t2 - 0.04153*t1 + 0.00516)
+            + (0.15346*t3 - 0.26756*t2 + 0.06670*t1 + 0.26688);
+
+        let perez = [
+            [0.1787*t - 1.4630, -0.3554*t + 0.4275, -0.0227*t + 5.3251, 0.1206*t - 2.5771, -0.0670*t + 0.3703],
+            [-0.0193*t - 0.2592, -0.0665*t + 0.0008, -0.0004*t + 0.2125, -0.0641*t - 0.8989, -0.0033*t + 0.0452],
+            [-0.0167*t - 0.2608, -0.0950*t + 0.0092, -0.0079*t + 0.2102, -0.0441*t - 1.6537, -0.0109*t + 0.0529],
+        ];
+
+        // transmittance of the atmosphere along the sun's optical mass, rayleigh and aerosol
+        // extinction at a red, green and blue wavelength in micrometers
+        let mass = 1.0 / (theta_s.cos() + 0.15*(93.885 - theta_s.to_degrees()).powf(-1.253));
+        let beta = 0.04608*t - 0.04586;
+        let transmittance = |lambda: f32| (-(0.008735*lambda.powf(-4.08) + beta*lambda.powf(-1.3))*mass).exp();
+        let sun_irradiance = Color::new(transmittance(0.65), transmittance(0.57), transmittance(0.475))
+            *(SUN_ILLUMINANCE*LUMINANCE_SCALE*if elevation > 0.0 { 1.0 } else { 0.0 });
+
+        Self { sun_direction, theta_s, zenith: [luminance, x, y], perez, sun_irradiance }
+
+    }
+
+    pub fn sun_direction(&self) -> Point {
+        self.sun_direction
+    }
+
+    // the sun matching the sky, to add to the lights of the world
+    pub fn sun(&self) -> Light {
+        DirectionalLight::new(self.sun_direction, self.sun_irradiance, SUN_DIAMETER)
+    }
+
+    // radiance of the sky dome towards direction, without the sun; below the horizon it is
+    // continued with the horizon values
+    pub fn radiance(&self, direction: Point) -> Color {
+
+        let direction = Point::unit_vector(&direction);
+        let cos_theta = direction.y().max(1e-3);
+        let gamma = direction.dot(self.sun_direction).clamp(-1.0, 1.0).acos();
+
+        let perez = |c: &[f32; 5], cos_theta: f32, gamma: f32| {
+            (1.0 + c[0]*(c[1] / cos_theta).exp())*(1.0 + c[2]*(c[3]*gamma).exp() + c[4]*gamma.cos()*gamma.cos())
+        };
+
+        let [luminance, x, y]: [f32; 3] = std::array::from_fn(|i| {
+            self.zenith[i]*perez(&self.perez[i], cos_theta, gamma) / perez(&self.perez[i], 1.0, self.theta_s)
+        });
+
+        if luminance <= 0.0 || y <= 0.0 { return Color::default() }
+
+        // xyY to linear sRGB
+        let luminance = luminance*LUMINANCE_SCALE;
+        let (cx, cz) = (x / y*luminance, (1.0 - x - y) / y*luminance);
+        let r = 3.2406*cx - 1.5372*luminance - 0.4986*cz;
+        let g = -0.9689*cx + 1.8758*luminance + 0.0415*cz;
+        let b = 0.0557*cx - 0.2040*luminance + 1.0570*cz;
+
+        return Color::new(r.max(0.0), g.max(0.0), b.max(0.0));
+
+    }
+
+    // radiance of the sun disk towards direction, zero outside of it
+    pub fn sun_radiance(&self, direction: Point) -> Color {
+
+        let cos_max = (SUN_DIAMETER.to_radians() / 2.0).cos();
+
+        if Point::unit_vector(&direction).dot(self.sun_direction) < cos_max { return Color::default() }
+
+        return self.sun_irradiance / (2.0*PI*(1.0 - cos_max));
+
+    }
+
+}
+
+//
+// tests
+#[test]
+fn test_sky(){
+
+    use crate::light::Illuminate;
+
+    let sky = Sky::new(45.0, 90.0, 3.0);
+    let up = Point::new(0.0, 1.0, 0.0);
+
+    assert_relative_eq!(sky.sun_direction().length(), 1.0, epsilon = 1e-5);
+    assert_relative_eq!(sky.sun_direction().z(), -(0.5f32).sqrt(), epsilon = 1e-5);
+
+    // blue at the zenith, brighter around the sun than away from it
+    let zenith = sky.radiance(up);
+    assert!(zenith.z() > zenith.y() && zenith.y() > zenith.x());
+    let towards = sky.radiance(Point::new(0.0, 0.3, -1.0));
+    let away = sky.radiance(Point::new(0.0, 0.3, 1.0));
+    assert!(towards.y() > away.y());
+
+    // the sun light has the irradiance of the disk, and reddens near the horizon
+    let (direction, distance, irradiance) = sky.sun().illuminate(Point::default()).unwrap();
+    assert!(direction.dot(sky.sun_direction()) > 0.999 && distance.is_infinite());
+    let solid_angle = 2.0*PI*(1.0 - (SUN_DIAMETER.to_radians() / 2.0).cos());
+    assert_relative_eq!(sky.sun_radiance(sky.sun_direction()).y()*solid_angle, irradiance.y(), max_relative = 1e-3);
+    assert_eq!(sky.sun_radiance(up), Color::default());
+
+    let sunset = Sky::new(3.0, 0.0, 3.0).sun().illuminate(Point::default()).unwrap().2;
+    assert!(sunset.x() > sunset.z() && sunset.y() < irradiance.y());
+
+    // more haze, a whiter sky
+    let hazy = Sky::new(45.0, 90.0, 8.0).radiance(up);
+    assert!(hazy.z() / hazy.x() < zenith.z() / zenith.x());
+
+}
@@ -0,0 +1,260 @@
+// published coefficients are kept verbatim
+#![allow(clippy::excessive_precision)]
+
+use std::sync::OnceLock;
+
+use crate::vec3::{Color, Vec3};
+
+// visible range sampled by the spectral renderer, in nm
+pub const LAMBDA_MIN: f32 = 380.0;
+pub const LAMBDA_MAX: f32 = 720.0;
+
+// representative wavelengths of the red, green and blue channels of the rgb renderer, in nm
+pub const RGB_WAVELENGTHS: [f32; 3] = [650.0, 550.0, 450.0];
+
+// step of the numerical integrations over the visible range, in nm
+const INTEGRATION_STEP: f32 = 1.0;
+
+//
+// Wavelengths struct
+// a hero wavelength and two companions rotated by a third of the range, carried along a path
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Wavelengths {
+    lambdas: Vec3,
+    terminated: bool,
+}
+
+impl Wavelengths {
+
+    pub fn sample(u: f32) -> Self {
+
+        let range = LAMBDA_MAX - LAMBDA_MIN;
+        let hero = u.clamp(0.0, 1.0) * range;
+        let rotate = |offset: f32| LAMBDA_MIN + (hero + offset*range/3.0) % range;
+
+        Self { lambdas: Vec3::new(rotate(0.0), rotate(1.0), rotate(2.0)), terminated: false }
+
+    }
+
+    pub fn hero(&self) -> f32 {self.lambdas.x()}
+
+    pub fn lambdas(&self) -> Vec3 {self.lambdas}
+
+    // wavelength dependent refraction splits the path, only the hero wavelength is kept
+    pub fn terminate_secondary(self) -> Self {
+        Self { lambdas: self.lambdas, terminated: true }
+    }
+
+    pub fn is_terminated(&self) -> bool {self.terminated}
+
+    // spectral values of an rgb color at the carried wavelengths
+    pub fn upsample(&self, color: Color) -> Color {
+        Vec3::new(upsample(color, self.lambdas.x()), upsample(color, self.lambdas.y()), upsample(color, self.lambdas.z()))
+    }
+
+    // monte carlo estimate of the rgb color of the spectral values at the carried wavelengths
+    pub fn to_rgb(&self, values: Color) -> Color {
+
+        let normalization = conversion().white;
+
+        let weight = |lambda: f32| xyz_to_rgb(cie_xyz(lambda)) / normalization;
+
+        // uniform wavelength pdf and three samples per path
+        let scale = (LAMBDA_MAX - LAMBDA_MIN) / 3.0;
+        let rgb = weight(self.lambdas.x())*values.x() + weight(self.lambdas.y())*values.y() + weight(self.lambdas.z())*values.z();
+
+        return rgb*scale;
+
+    }
+
+}
+
+//
+// CIE 1931 color matching functions, multi-lobe fit from Wyman, Sloan and Shirley 2013
+pub fn cie_xyz(lambda: f32) -> Vec3 {
+
+    let g = |mu: f32, sigma_low: f32, sigma_high: f32| {
+        let sigma = if lambda < mu { sigma_low } else { sigma_high };
+        let t = (lambda - mu) / sigma;
+        (-0.5*t*t).exp()
+    };
+
+    let x = 1.056*g(599.8, 37.9, 31.0) + 0.362*g(442.0, 16.0, 26.7) - 0.065*g(501.1, 20.4, 26.2);
+    let y = 0.821*g(568.8, 46.9, 40.5) + 0.286*g(530.9, 16.3, 31.1);
+    let z = 1.217*g(437.0, 11.8, 36.0) + 0.681*g(459.0, 26.0, 13.8);
+
+    return Vec3::new(x, y, z);
+
+}
+
+// CIE XYZ to linear sRGB
+pub fn xyz_to_rgb(xyz: Vec3) -> Color {
+    Color::new(
+        3.2404542*xyz.x() - 1.5371385*xyz.y() - 0.4985314*xyz.z(),
+        -0.9692660*xyz.x() + 1.8760108*xyz.y() + 0.0415560*xyz.z(),
+        0.0556434*xyz.x() - 0.2040259*xyz.y() + 1.0572252*xyz.z(),
+    )
+}
+
+//
+// rgb to spectrum upsampling
+// smooth red, green and blue basis spectra summing to one, so white stays flat and
+// reflectances stay in [0:1], with coefficients corrected so that the round trip is exact
+
+fn basis(lambda: f32) -> Vec3 {
+
+    let g = |mu: f32, sigma: f32| {
+        let t = (lambda - mu) / sigma;
+        (-0.5*t*t).exp()
+    };
+
+    let b = Vec3::new(g(620.0, 40.0), g(540.0, 35.0), g(460.0, 30.0));
+    let sum = b.x() + b.y() + b.z();
+
+    return b / sum;
+
+}
+
+pub fn upsample(color: Color, lambda: f32) -> f32 {
+
+    let inverse = &conversion().inverse;
+    let coefficients = Vec3::new(inverse[0].dot(color), inverse[1].dot(color), inverse[2].dot(color));
+
+    return coefficients.dot(basis(lambda)).max(0.0);
+
+}
+
+struct Conversion {
+    // rgb of the flat unit spectrum, before normalization
+    white: Color,
+    // inverse of the round trip matrix, rows
+    inverse: [Vec3; 3],
+}
+
+fn conversion() -> &'static Conversion {
+
+    static CONVERSION: OnceLock<Conversion> = OnceLock::new();
+
+    CONVERSION.get_or_init(|| {
+
+        let steps = ((LAMBDA_MAX - LAMBDA_MIN) / INTEGRATION_STEP) as usize;
+        let lambda = |i: usize| LAMBDA_MIN + (i as f32 + 0.5)*INTEGRATION_STEP;
+
+        let white: Color = (0..steps).map(|i| xyz_to_rgb(cie_xyz(lambda(i)))*INTEGRATION_STEP).sum();
+
+        // round trip matrix, column j is the normalized rgb of basis spectrum j
+        let mut columns = [Vec3::default(); 3];
+        for i in 0..steps {
+            let rgb = xyz_to_rgb(cie_xyz(lambda(i)))*INTEGRATION_STEP / white;
+            let b = basis(lambda(i));
+            columns[0] = columns[0] + rgb*b.x();
+            columns[1] = columns[1] + rgb*b.y();
+            columns[2] = columns[2] + rgb*b.z();
+        }
+
+        // inverse of a 3x3 matrix from its columns, rows are the cross products over the determinant
+        let determinant = columns[0].dot(columns[1].cross(columns[2]));
+        let inverse = [
+            columns[1].cross(columns[2]) / determinant,
+            columns[2].cross(columns[0]) / determinant,
+            columns[0].cross(columns[1]) / determinant,
+        ];
+
+        Conversion { white, inverse }
+
+    })
+
+}
+
+//
+// wavelength dependent index of refraction, wavelengths in nm
+#[derive(Debug, Clone, Copy)]
+pub enum Dispersion {
+    // n = A + B/lambda^2, B in um^2
+    Cauchy(f32, f32),
+    // n^2 = 1 + sum B_i lambda^2/(lambda^2 - C_i), C_i in um^2
+    Sellmeier([f32; 3], [f32; 3]),
+}
+
+impl Dispersion {
+
+    pub fn bk7() -> Self {
+        Self::Sellmeier([1.03961212, 0.231792344, 1.01046945], [0.00600069867, 0.0200179144, 103.560653])
+    }
+
+    pub fn dense_flint() -> Self {
+        Self::Cauchy(1.7280, 0.01342)
+    }
+
+    pub fn diamond() -> Self {
+        Self::Cauchy(2.3850, 0.0117)
+    }
+
+    pub fn refraction_index(&self, lambda: f32) -> f32 {
+
+        let lambda2 = (lambda / 1000.0).powi(2);
+
+        match self {
+            Self::Cauchy(a, b) => a + b / lambda2,
+            Self::Sellmeier(b, c) => {
+                let sum: f32 = (0..3).map(|i| b[i]*lambda2 / (lambda2 - c[i])).sum();
+                (1.0 + sum).sqrt()
+            }
+        }
+
+    }
+
+}
+
+//
+// tests
+#[test]
+fn test_upsample(){
+
+    // white is flat, and every color survives the round trip
+    let white = Color::new(1.0, 1.0, 1.0);
+    for lambda in [400.0, 500.0, 600.0, 700.0] {
+        assert_relative_eq!(upsample(white, lambda), 1.0, epsilon = 1e-4);
+    }
+
+    for color in [white, Color::new(0.8, 0.4, 0.2), Color::new(0.1, 0.5, 0.3)] {
+
+        let steps = ((LAMBDA_MAX - LAMBDA_MIN) / INTEGRATION_STEP) as usize;
+        let rgb: Color = (0..steps).map(|i| {
+            let lambda = LAMBDA_MIN + (i as f32 + 0.5)*INTEGRATION_STEP;
+            xyz_to_rgb(cie_xyz(lambda)) * upsample(color, lambda) * INTEGRATION_STEP
+        }).sum();
+        let rgb = rgb / conversion().white;
+
+        assert_relative_eq!((rgb - color).length(), 0.0, epsilon = 1e-3);
+
+    }
+
+    // and the monte carlo estimate converges to it
+    let color = Color::new(0.8, 0.4, 0.2);
+    let n = 10000;
+    let estimate: Color = (0..n).map(|i| {
+        let wavelengths = Wavelengths::sample((i as f32 + 0.5) / (n as f32));
+        wavelengths.to_rgb(wavelengths.upsample(color))
+    }).sum();
+    assert_relative_eq!((estimate / (n as f32) - color).length(), 0.0, epsilon = 1e-2);
+
+}
+
+#[test]
+fn test_dispersion(){
+
+    // BK7 at the sodium d-line and dispersion towards the blue
+    let bk7 = Dispersion::bk7();
+    assert_relative_eq!(bk7.refraction_index(587.6), 1.5168, epsilon = 1e-3);
+    assert!(bk7.refraction_index(450.0) > bk7.refraction_index(650.0));
+
+    let diamond = Dispersion::diamond();
+    assert_relative_eq!(diamond.refraction_index(589.0), 2.419, epsilon = 2e-3);
+
+    let wavelengths = Wavelengths::sample(0.5);
+    assert_relative_eq!(wavelengths.hero(), 550.0);
+    assert!(!wavelengths.is_terminated());
+    assert!(wavelengths.terminate_secondary().is_terminated());
+
+}
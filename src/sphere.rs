@@ -1,7 +1,10 @@
 use crate::{vec3::Point, ray::Ray, interval::Interval};
 use crate::hittable::{Hittable, HitRecord, HittableObject};
+use crate::light::{AreaLight, Light};
 use crate::material::Material;
 
+use std::f32::consts::PI;
+
 pub struct Sphere {
     center: Point,
     radius: f32,
@@ -13,12 +16,43 @@ impl Sphere {
     pub fn new(center: Point, radius: f32, material: Material) -> HittableObject {
         HittableObject::Sphere(Self { center, radius, material })
     }
+
+    pub fn area_light(&self) -> Option<Light> {
+        self.material.emission().map(|radiance| AreaLight::sphere(self.center, self.radius, radiance))
+    }
+
+    fn surface_coordinates(&self, normal: Point) -> (f32, f32, Point, Point) {
+
+        // spherical mapping of the outward unit normal:
+        // u = phi/2pi, phi around the y-axis starting from -x
+        // v = theta/pi, theta from -y (south pole) to +y (north pole)
+        let theta = (-normal.y()).clamp(-1.0, 1.0).acos();
+        let phi = (-normal.z()).atan2(normal.x()) + PI;
+
+        let u = phi / (2.0*PI);
+        let v = theta / PI;
+
+        // partial derivatives of the surface position, dpdu x dpdv points outwards
+        // sin(theta) is clamped so that the poles still get a usable frame
+        let sin_theta = theta.sin().max(1e-4);
+        let cos_theta = -normal.y();
+
+        let dpdu = Point::new(normal.z(), 0.0, -normal.x()) * (2.0*PI*self.radius);
+        let dpdv = Point::new(
+            normal.x()*cos_theta/sin_theta,
+            sin_theta,
+            normal.z()*cos_theta/sin_theta,
+        ) * (PI*self.radius);
+
+        return (u, v, dpdu, dpdv);
+
+    }
     
 }
 
 impl Hittable for Sphere {
     
-    fn hit(&self, ray: &Ray, interval: Interval) -> Option<(HitRecord, Material)> {
+    fn hit(&self, ray: &Ray, interval: Interval) -> Option<(HitRecord, &Material)> {
         
         // hit logic
         let oc = self.center - ray.origin();
@@ -46,9 +80,10 @@ impl Hittable for Sphere {
         // generate record
         let hit_location = ray.at(root);
         let normal = (hit_location - self.center) / self.radius; // outward normal
-        let record = HitRecord::new(hit_location, normal, root, ray);
+        let (u, v, dpdu, dpdv) = self.surface_coordinates(normal);
+        let record = HitRecord::new(hit_location, normal, root, ray, u, v, dpdu, dpdv);
 
-        return Some((record, self.material));
+        return Some((record, &self.material));
 
     }
 
@@ -84,3 +119,47 @@ fn test_hit(){
     assert_relative_eq!(record.t, 2.0);
 
 }
+
+#[test]
+fn test_surface_coordinates(){
+
+    use crate::interval::Interval;
+
+    let radius = 2.0;
+    let center = Point::new(0.0, 0.0, 0.0);
+    let material = crate::material::Lambertian::new(Point::new(0.5, 0.5, 0.5));
+    let sphere = Sphere::new(center, radius, material);
+
+    // hit at (2, 0, 0): equator, phi = pi
+    let ray = Ray::new(Point::new(4.0, 0.0, 0.0), Point::new(-1.0, 0.0, 0.0));
+    let interval = Interval::universe().set_min(0.0);
+    let (record, _) = sphere.hit(&ray, interval).expect("There should be a hit.");
+
+    assert_relative_eq!(record.u, 0.5);
+    assert_relative_eq!(record.v, 0.5);
+
+    // tangents follow the parameterization and span the tangent plane
+    assert_relative_eq!(record.dpdu.dot(record.normal), 0.0, epsilon = 1e-5);
+    assert_relative_eq!(record.dpdv.dot(record.normal), 0.0, epsilon = 1e-5);
+    assert_relative_eq!(record.dpdu.length(), 2.0*PI*radius, epsilon = 1e-4);
+    assert_relative_eq!(record.dpdv.length(), PI*radius, epsilon = 1e-4);
+    assert!(record.dpdu.cross(record.dpdv).dot(record.normal) > 0.0);
+
+    // finite difference check: moving along u moves the point along dpdu
+    let hit_at = |u: f32, v: f32| {
+        let phi = 2.0*PI*u;
+        let theta = PI*v;
+        Point::new(-phi.cos()*theta.sin(), -theta.cos(), phi.sin()*theta.sin()) * radius
+    };
+    let du = 1e-3;
+    let step = (hit_at(record.u + du, record.v) - hit_at(record.u, record.v)) / du;
+    assert_relative_eq!((step - record.dpdu).length(), 0.0, epsilon = 1e-1);
+    let step = (hit_at(record.u, record.v + du) - hit_at(record.u, record.v)) / du;
+    assert_relative_eq!((step - record.dpdv).length(), 0.0, epsilon = 1e-1);
+
+    // north pole maps to v = 1
+    let ray = Ray::new(Point::new(0.0, 4.0, 0.0), Point::new(0.0, -1.0, 0.0));
+    let (record, _) = sphere.hit(&ray, interval).expect("There should be a hit.");
+    assert_relative_eq!(record.v, 1.0);
+
+}
@@ -0,0 +1,409 @@
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::sync::Arc;
+
+use crate::noise::Perlin;
+use crate::vec3::{Color, Point};
+
+//
+// main trait
+pub trait Lookup {
+
+    fn value(&self, u: f32, v: f32, p: Point) -> Color;
+
+}
+
+#[derive(Debug, Clone)]
+pub enum Texture {
+    Solid(Color),
+    Image(Image),
+    Noise(Noise),
+    Checker(Checker),
+}
+
+impl Lookup for Texture {
+
+    fn value(&self, u: f32, v: f32, p: Point) -> Color {
+        match self {
+            Self::Solid(c) => *c,
+            Self::Image(i) => i.value(u, v, p),
+            Self::Noise(n) => n.value(p),
+            Self::Checker(c) => c.value(u, v),
+            // Handle other textures here
+        }
+    }
+
+}
+
+impl Texture {
+
+    // mean color over the surface, e.g. for the uniform area light of a masked emitter
+    pub fn average(&self) -> Color {
+        match self {
+            Self::Solid(c) => *c,
+            Self::Image(i) => i.pixels.iter().fold(Color::default(), |sum, &c| sum + c) / i.pixels.len().max(1) as f32,
+            Self::Noise(n) => n.average(),
+            Self::Checker(c) => (c.color_a + c.color_b) / 2.0,
+        }
+    }
+
+}
+
+impl From<Color> for Texture {
+
+    fn from(color: Color) -> Self {
+        Self::Solid(color)
+    }
+
+}
+
+//
+// Image
+#[derive(Debug, Clone)]
+pub struct Image {
+
+    width: usize,
+    height: usize,
+    pixels: Arc<Vec<Color>>,
+
+}
+
+impl Image {
+
+    pub fn new(width: usize, height: usize, pixels: Vec<Color>) -> Texture {
+
+        assert_eq!(pixels.len(), width*height, "Image size does not match the pixel count.");
+
+        Texture::Image(Self { width, height, pixels: Arc::new(pixels) })
+
+    }
+
+    pub fn load_ppm<P: AsRef<Path>>(path: P) -> io::Result<Texture> {
+
+        let bytes = fs::read(path)?;
+
+        return Image::parse_ppm(&bytes);
+
+    }
+
+    pub fn parse_ppm(bytes: &[u8]) -> io::Result<Texture> {
+
+        // values are returned as stored in [0:1] without any gamma decoding,
+        // which is what normal and height maps expect
+        let invalid = |msg: &str| io::Error::new(io::ErrorKind::InvalidData, msg.to_string());
+
+        // header: magic, width, height, max value, with '#' comments in between
+        let mut position = 0;
+        let mut header = Vec::new();
+
+        while header.len() < 4 {
+
+            while position < bytes.len() && bytes[position].is_ascii_whitespace() { position += 1 }
+
+            if position >= bytes.len() { return Err(invalid("Truncated PPM header.")) }
+
+            if bytes[position] == b'#' {
+                while position < bytes.len() && bytes[position] != b'\n' { position += 1 }
+                continue;
+            }
+
+            let start = position;
+            while position < bytes.len() && !bytes[position].is_ascii_whitespace() { position += 1 }
+            header.push(String::from_utf8_lossy(&bytes[start..position]).into_owned());
+
+        }
+
+        let parse = |s: &str| s.parse::<usize>().map_err(|_| invalid("Invalid PPM header value."));
+        let width = parse(&header[1])?;
+        let height = parse(&header[2])?;
+        let max_value = parse(&header[3])?;
+
+        if max_value == 0 || max_value > 65535 { return Err(invalid("Invalid PPM max value.")) }
+
+        if width == 0 || height == 0 { return Err(invalid("Empty PPM image.")) }
+
+        let count = width.checked_mul(height).and_then(|n| n.checked_mul(3))
+            .ok_or_else(|| invalid("PPM image too large."))?;
+        let scale = 1.0 / (max_value as f32);
+
+        let samples: Vec<f32> = match header[0].as_str() {
+
+            "P3" => {
+
+                let samples = String::from_utf8_lossy(&bytes[position..])
+                    .lines()
+                    .map(|line| line.split('#').next().unwrap_or(""))
+                    .flat_map(|line| line.split_whitespace().map(str::to_owned).collect::<Vec<_>>())
+                    .take(count)
+                    .map(|s| parse(&s).map(|x| (x as f32) * scale))
+                    .collect::<io::Result<Vec<f32>>>()?;
+
+                samples
+
+            }
+
+            "P6" => {
+
+                // a single whitespace separates the header from the binary data
+                let data = &bytes[(position + 1).min(bytes.len())..];
+                let bytes_per_sample = if max_value < 256 { 1 } else { 2 };
+
+                let size = count.checked_mul(bytes_per_sample).ok_or_else(|| invalid("PPM image too large."))?;
+
+                if data.len() < size { return Err(invalid("Truncated PPM data.")) }
+
+                (0..count).map(|i| {
+                    let x = if bytes_per_sample == 1 { data[i] as usize } else { ((data[2*i] as usize) << 8) | data[2*i + 1] as usize };
+                    (x as f32) * scale
+                }).collect()
+
+            }
+
+            _ => return Err(invalid("Unsupported image format, expected P3 or P6.")),
+
+        };
+
+        if samples.len() < count { return Err(invalid("Truncated PPM data.")) }
+
+        let pixels = samples.chunks(3).map(|c| Color::new(c[0], c[1], c[2])).collect();
+
+        return Ok(Image::new(width, height, pixels));
+
+    }
+
+    fn pixel(&self, i: usize, j: usize) -> Color {
+        self.pixels[j*self.width + i]
+    }
+
+    fn value(&self, u: f32, v: f32, _p: Point) -> Color {
+
+        // bilinear filtering with wrap-around, v = 0 is the bottom row of the image
+        let x = u.rem_euclid(1.0) * (self.width as f32) - 0.5;
+        let y = (1.0 - v.rem_euclid(1.0)) * (self.height as f32) - 0.5;
+
+        let x0 = x.floor();
+        let y0 = y.floor();
+        let fx = x - x0;
+        let fy = y - y0;
+
+        let wrap = |a: f32, n: usize| (a as i64).rem_euclid(n as i64) as usize;
+        let i0 = wrap(x0, self.width);
+        let i1 = wrap(x0 + 1.0, self.width);
+        let j0 = wrap(y0, self.height);
+        let j1 = wrap(y0 + 1.0, self.height);
+
+        let top = self.pixel(i0, j0)*(1.0 - fx) + self.pixel(i1, j0)*fx;
+        let bottom = self.pixel(i0, j1)*(1.0 - fx) + self.pixel(i1, j1)*fx;
+
+        return top*(1.0 - fy) + bottom*fy;
+
+    }
+
+}
+
+//
+// procedural noise patterns
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Pattern {
+    Perlin,
+    Fbm,
+    Turbulence,
+    Worley,
+    Warped,
+    Marble,
+    Wood,
+    Stone,
+}
+
+//
+// Noise
+#[derive(Debug, Clone)]
+pub struct Noise {
+
+    perlin: Arc<Perlin>,
+    pattern: Pattern,
+    scale: f32,
+    color_a: Color,
+    color_b: Color,
+
+}
+
+impl Noise {
+
+    pub fn new(pattern: Pattern, scale: f32, color_a: Color, color_b: Color) -> Texture {
+        Noise::seeded(pattern, scale, color_a, color_b, 0)
+    }
+
+    pub fn seeded(pattern: Pattern, scale: f32, color_a: Color, color_b: Color, seed: u64) -> Texture {
+        Texture::Noise(Self { perlin: Arc::new(Perlin::new(seed)), pattern, scale, color_a, color_b })
+    }
+
+    // blend factor in [0:1] between the two colors
+    pub fn factor(&self, p: Point) -> f32 {
+
+        let p = p*self.scale;
+        let perlin = &self.perlin;
+
+        let t = match self.pattern {
+
+            Pattern::Perlin => 0.5*(1.0 + perlin.noise(p)),
+            Pattern::Fbm => 0.5*(1.0 + perlin.fbm(p, 6, 2.0, 0.5)),
+            Pattern::Turbulence => perlin.turbulence(p, 7),
+            Pattern::Worley => perlin.worley(p).0,
+            Pattern::Warped => 0.5*(1.0 + 2.0*perlin.warped(p, 4.0, 5)),
+
+            // veins from a sine wave distorted by turbulence
+            Pattern::Marble => 0.5*(1.0 + (p.z() + 10.0*perlin.turbulence(p, 7)).sin()),
+
+            // growth rings around the y-axis, wobbled by low frequency noise
+            Pattern::Wood => {
+                let radius = (p.x()*p.x() + p.z()*p.z()).sqrt() + 0.4*perlin.fbm(p*0.5, 3, 2.0, 0.5);
+                let rings = (radius*4.0).rem_euclid(1.0);
+                rings*rings*(3.0 - 2.0*rings)
+            }
+
+            // cells with dark cracks along the cell borders and mottled faces
+            Pattern::Stone => {
+                let (f1, f2) = perlin.worley(p);
+                let crack = ((f2 - f1)*8.0).min(1.0);
+                let mottle = 0.5*(1.0 + perlin.fbm(p*4.0, 4, 2.0, 0.5));
+                crack*(0.6 + 0.4*mottle)
+            }
+
+        };
+
+        return t.clamp(0.0, 1.0);
+
+    }
+
+    // over a fixed lattice spread across a few noise cells, off the integer points where perlin noise vanishes
+    fn average(&self) -> Color {
+
+        let n = 8;
+        let t = (0..n*n*n)
+            .map(|i| self.factor(Point::new((i % n) as f32, (i / n % n) as f32, (i / (n*n)) as f32)*(0.731 / self.scale)))
+            .sum::<f32>() / (n*n*n) as f32;
+
+        return self.color_a*(1.0 - t) + self.color_b*t;
+
+    }
+
+    fn value(&self, p: Point) -> Color {
+
+        let t = self.factor(p);
+
+        return self.color_a*(1.0 - t) + self.color_b*t;
+
+    }
+
+}
+
+//
+// Checker, squares alternating over the surface coordinates
+#[derive(Debug, Clone)]
+pub struct Checker {
+
+    // squares per unit of u and v
+    scale: f32,
+    color_a: Color,
+    color_b: Color,
+
+}
+
+impl Checker {
+
+    pub fn new(scale: f32, color_a: Color, color_b: Color) -> Texture {
+        Texture::Checker(Self { scale, color_a, color_b })
+    }
+
+    fn value(&self, u: f32, v: f32) -> Color {
+
+        let parity = ((u*self.scale).floor() + (v*self.scale).floor()).rem_euclid(2.0);
+
+        return if parity < 1.0 { self.color_a } else { self.color_b };
+
+    }
+
+}
+
+//
+// tests
+#[test]
+fn test_parse_ppm(){
+
+    let ascii = "P3\n# a comment\n2 1\n255\n255 0 0  0 0 255\n";
+    let texture = Image::parse_ppm(ascii.as_bytes()).expect("Valid PPM.");
+
+    // sample the pixel centers
+    assert_eq!(texture.value(0.25, 0.5, Point::default()), Color::new(1.0, 0.0, 0.0));
+    assert_eq!(texture.value(0.75, 0.5, Point::default()), Color::new(0.0, 0.0, 1.0));
+
+    let mut binary = b"P6 1 2 255\n".to_vec();
+    binary.extend([0, 255, 0, 51, 51, 51]);
+    let texture = Image::parse_ppm(&binary).expect("Valid PPM.");
+
+    // the first row is the top of the image
+    assert_eq!(texture.value(0.5, 0.75, Point::default()), Color::new(0.0, 1.0, 0.0));
+    assert_eq!(texture.value(0.5, 0.25, Point::default()), Color::new(0.2, 0.2, 0.2));
+
+    assert!(Image::parse_ppm(b"P5 1 1 255\n\0").is_err());
+    assert!(Image::parse_ppm(b"P3 2 2 255\n0 0 0").is_err());
+
+    // no pixels, or more than can be counted
+    assert!(Image::parse_ppm(b"P3 0 1 255\n").is_err());
+    assert!(Image::parse_ppm(b"P6 1 0 255\n").is_err());
+    assert!(Image::parse_ppm(format!("P6 {} {} 255\n", usize::MAX / 2, 2).as_bytes()).is_err());
+    assert!(Image::parse_ppm(format!("P6 {} {} 65535\n", usize::MAX / 4, 1).as_bytes()).is_err());
+
+}
+
+#[test]
+fn test_noise(){
+
+    let black = Color::new(0.0, 0.0, 0.0);
+    let white = Color::new(1.0, 1.0, 1.0);
+
+    let patterns = [Pattern::Perlin, Pattern::Fbm, Pattern::Turbulence, Pattern::Worley,
+        Pattern::Warped, Pattern::Marble, Pattern::Wood, Pattern::Stone];
+
+    for pattern in patterns {
+
+        let texture = Noise::new(pattern, 3.0, black, white);
+
+        for i in 0..200 {
+            let p = Point::new(i as f32 * 0.071, i as f32 * -0.013, i as f32 * 0.037);
+            let c = texture.value(0.0, 0.0, p);
+            assert!((0.0..=1.0).contains(&c.x()));
+            assert_eq!(c.x(), c.y());
+        }
+
+        // the mean lies strictly between the two colors
+        let average = texture.average().x();
+        assert!(average > 0.05 && average < 0.95, "{:?} averages {}", pattern, average);
+
+    }
+
+    // same seed, same texture
+    let p = Point::new(0.4, 0.2, -0.9);
+    let a = Noise::seeded(Pattern::Marble, 2.0, black, white, 3);
+    let b = Noise::seeded(Pattern::Marble, 2.0, black, white, 3);
+    assert_eq!(a.value(0.0, 0.0, p), b.value(0.0, 0.0, p));
+
+}
+
+#[test]
+fn test_checker(){
+
+    let black = Color::new(0.0, 0.0, 0.0);
+    let white = Color::new(1.0, 1.0, 1.0);
+    let texture = Checker::new(2.0, black, white);
+
+    // half unit squares, also across negative coordinates
+    assert_eq!(texture.value(0.25, 0.25, Point::default()), black);
+    assert_eq!(texture.value(0.75, 0.25, Point::default()), white);
+    assert_eq!(texture.value(0.75, 0.75, Point::default()), black);
+    assert_eq!(texture.value(-0.25, 0.25, Point::default()), white);
+    assert_eq!(texture.value(-0.25, -0.25, Point::default()), black);
+
+}
@@ -44,6 +44,10 @@ impl Vec3{
         self.0*other.0 + self.1*other.1 + self.2*other.2
     }
 
+    pub fn exp(&self) -> Self {
+        Self(self.0.exp(), self.1.exp(), self.2.exp())
+    }
+
     pub fn cross(&self, other: Self) -> Self {
         Self(
             self.1*other.2 - self.2*other.1,
@@ -299,6 +303,7 @@ impl Color{
 //
 // tests
 #[test]
+#[allow(clippy::clone_on_copy)]
 fn test_add(){
     let a = Vec3::new(1.0, 0.5, 0.0);
     let b = Vec3::new(1.0, -1.0, 1.0);
@@ -308,6 +313,7 @@ fn test_add(){
 }
 
 #[test]
+#[allow(clippy::clone_on_copy)]
 fn test_sub(){
     let a = Vec3::new(1.0, 0.5, 0.0);
     let b = Vec3::new(1.0, -1.0, 1.0);
@@ -317,6 +323,7 @@ fn test_sub(){
 }
 
 #[test]
+#[allow(clippy::clone_on_copy)]
 fn test_mul(){
     let a = Vec3::new(1.0, 0.5, 0.0);
     let b = Vec3::new(1.0, -1.0, 1.0);
@@ -326,6 +333,7 @@ fn test_mul(){
 }
 
 #[test]
+#[allow(clippy::clone_on_copy)]
 fn test_div(){
     let a = Vec3::new(1.0, 0.5, 0.0);
     let b = Vec3::new(2.0, -1.0, 1.0);
@@ -376,6 +384,13 @@ fn test_cross(){
     assert_eq!(a.cross(b), Vec3::new(0.5, -1.0, -2.0));
 }
 
+#[test]
+fn test_exp(){
+    let a = Vec3::new(0.0, 1.0, -1.0);
+
+    assert_eq!(a.exp(), Vec3::new(1.0, std::f32::consts::E, 1.0/std::f32::consts::E));
+}
+
 #[test]
 fn test_display(){
     let a = Vec3::new(1.0, 0.5, 0.0);